@@ -1,21 +1,127 @@
 interface Piece {
     composer: string;
+    composer_id: number;
     title: string;
     piece_id: number;
+    created_by: number | null;
+    catalogue_number: string | null;
+    key: string | null;
+    instrumentation: string | null;
+    genre: string | null;
+    parent_piece_id: number | null;
+    movement_number: number | null;
+    open_opus_id: number | null;
+}
+
+type RepertoireStatus =
+    | "wishlist"
+    | "learning"
+    | "polishing"
+    | "performance_ready"
+    | "retired";
+
+interface TempoEntry {
+    tempo_entry_id: number;
+    practice_session_id: number;
+    piece_id: number;
+    passage: string | null;
+    target_bpm: number | null;
+    achieved_bpm: number;
+}
+
+interface PiecePracticed extends Piece {
+    duration_mins: number | null;
+    start_offset_mins: number | null;
+    notes: string | null;
+    repertoire_status: RepertoireStatus | null;
+    tempo_entries: TempoEntry[];
 }
 
 interface PracticeSession {
     start_datetime: string;
     duration_mins: number;
+    instrument_id: number;
     instrument: string;
-    pieces_practiced: Piece[];
+    pieces_practiced: PiecePracticed[];
     practice_session_id: number;
     user_id: number;
+    notes: string | null;
+    rating: number | null;
+    tags: string[];
 }
 
 interface PiecePracticedMapping {
     practice_session_id: number;
     piece_id: number;
+    duration_mins: number | null;
+    start_offset_mins: number | null;
+    notes: string | null;
+}
+
+interface RepertoireEntry {
+    user_id: number;
+    piece_id: number;
+    status: RepertoireStatus;
+    target_date: string | null;
+    notes: string | null;
+    updated_at: string;
+    piece: Piece;
+}
+
+type InstrumentFamily =
+    | "strings"
+    | "woodwinds"
+    | "brass"
+    | "percussion"
+    | "keyboard"
+    | "voice"
+    | "other";
+
+interface Instrument {
+    instrument_id: number;
+    user_id: number;
+    name: string;
+    family: InstrumentFamily | null;
+    notes: string | null;
+}
+
+interface Tag {
+    tag_id: number;
+    user_id: number;
+    name: string;
+}
+
+interface PracticeTimerSegment {
+    segment_id: number;
+    user_id: number;
+    piece_id: number | null;
+    start_secs: number;
+}
+
+interface PracticeTimer {
+    user_id: number;
+    instrument_id: number;
+    instrument: Instrument;
+    started_at: string;
+    paused_at: string | null;
+    paused_secs: number;
+    elapsed_secs: number;
+    paused: boolean;
+    piece_id: number | null;
+    segments: PracticeTimerSegment[];
 }
 
-export { Piece, PracticeSession, PiecePracticedMapping };
+export {
+    Piece,
+    PiecePracticed,
+    TempoEntry,
+    PracticeSession,
+    PiecePracticedMapping,
+    RepertoireStatus,
+    RepertoireEntry,
+    InstrumentFamily,
+    Instrument,
+    Tag,
+    PracticeTimerSegment,
+    PracticeTimer,
+};
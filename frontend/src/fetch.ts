@@ -108,7 +108,8 @@ const addPracticeSession: PostFn<
             "Content-Type": "application/json",
         },
         body: JSON.stringify({
-            start_datetime: practiceSession.startDatetime + ":00",
+            // the input is local time, so convert it to an unambiguous utc timestamp
+            start_datetime: new Date(practiceSession.startDatetime).toISOString(),
             duration_mins: practiceSession.durationMins,
             instrument: practiceSession.instrument,
             pieces_practiced: practiceSession.piecesPracticed,
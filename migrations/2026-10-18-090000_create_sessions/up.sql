@@ -0,0 +1,8 @@
+CREATE TABLE sessions (
+    session_id VARCHAR(64) NOT NULL,
+    session_data TEXT NOT NULL,
+    expires_at TIMESTAMPTZ,
+    PRIMARY KEY (session_id)
+);
+
+CREATE INDEX sessions_expires_at_idx ON sessions(expires_at);
@@ -0,0 +1,3 @@
+ALTER TABLE pieces_practiced
+    DROP COLUMN start_offset_mins,
+    DROP COLUMN duration_mins;
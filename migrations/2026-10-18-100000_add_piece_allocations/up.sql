@@ -0,0 +1,3 @@
+ALTER TABLE pieces_practiced
+    ADD COLUMN duration_mins INT CHECK (duration_mins >= 0),
+    ADD COLUMN start_offset_mins INT CHECK (start_offset_mins >= 0);
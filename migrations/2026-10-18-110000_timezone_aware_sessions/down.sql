@@ -0,0 +1,5 @@
+ALTER TABLE users
+    DROP COLUMN timezone;
+
+ALTER TABLE practice_sessions
+    ALTER COLUMN start_datetime TYPE TIMESTAMP USING start_datetime AT TIME ZONE 'UTC';
@@ -0,0 +1,6 @@
+-- existing start times carry no zone information, so they are assumed to be in UTC
+ALTER TABLE practice_sessions
+    ALTER COLUMN start_datetime TYPE TIMESTAMPTZ USING start_datetime AT TIME ZONE 'UTC';
+
+ALTER TABLE users
+    ADD COLUMN timezone VARCHAR(64) NOT NULL DEFAULT 'UTC';
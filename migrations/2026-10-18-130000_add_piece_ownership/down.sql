@@ -0,0 +1,5 @@
+ALTER TABLE pieces
+    DROP COLUMN created_by;
+
+ALTER TABLE users
+    DROP COLUMN role;
@@ -0,0 +1,7 @@
+ALTER TABLE users
+    ADD COLUMN role VARCHAR(20) NOT NULL DEFAULT 'user' CHECK (role IN ('user', 'admin'));
+
+-- pieces loaded from open opus (and created before this migration) have no creator,
+-- so only admins can edit or delete them
+ALTER TABLE pieces
+    ADD COLUMN created_by INT REFERENCES users(user_id) ON DELETE SET NULL;
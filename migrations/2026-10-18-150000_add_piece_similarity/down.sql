@@ -0,0 +1,6 @@
+DROP INDEX pieces_title_trgm_idx;
+
+DROP FUNCTION normalize_text(text);
+
+DROP EXTENSION IF EXISTS unaccent;
+DROP EXTENSION IF EXISTS pg_trgm;
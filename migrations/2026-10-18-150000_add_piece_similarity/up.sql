@@ -0,0 +1,13 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+-- lowercased, unaccented and with punctuation collapsed to spaces, so "No.1" compares like "No. 1";
+-- naming the unaccent dictionary explicitly is what makes this safe to mark immutable and index
+CREATE FUNCTION normalize_text(text) RETURNS text AS $$
+    SELECT trim(regexp_replace(
+        lower(public.unaccent('public.unaccent'::regdictionary, $1)),
+        '[^[:alnum:]]+', ' ', 'g'
+    ))
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT;
+
+CREATE INDEX pieces_title_trgm_idx ON pieces USING gin (normalize_text(title) gin_trgm_ops);
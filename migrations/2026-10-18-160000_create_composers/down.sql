@@ -0,0 +1,17 @@
+ALTER TABLE pieces
+    ADD COLUMN composer VARCHAR(100);
+
+UPDATE pieces
+SET composer = composers.name
+FROM composers
+WHERE composers.composer_id = pieces.composer_id;
+
+DROP INDEX pieces_composer_id_idx;
+
+ALTER TABLE pieces
+    ALTER COLUMN composer SET NOT NULL,
+    DROP CONSTRAINT pieces_title_composer_id_key,
+    DROP COLUMN composer_id,
+    ADD UNIQUE (title, composer);
+
+DROP TABLE composers;
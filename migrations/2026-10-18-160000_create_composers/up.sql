@@ -0,0 +1,35 @@
+CREATE TABLE composers (
+    composer_id SERIAL NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    sort_name VARCHAR(100) NOT NULL, -- e.g. "Bach, Johann Sebastian"
+    birth_year INT,
+    death_year INT,
+    era VARCHAR(40),
+    open_opus_id INT,
+    UNIQUE(name),
+    UNIQUE(open_opus_id),
+    PRIMARY KEY (composer_id)
+);
+
+CREATE INDEX composers_name_trgm_idx ON composers USING gin (normalize_text(name) gin_trgm_ops);
+
+-- existing composer names become composers as they are, sorted by their last word
+INSERT INTO composers (name, sort_name)
+SELECT DISTINCT composer, regexp_replace(composer, '^(.*\S)\s+(\S+)$', '\2, \1')
+FROM pieces;
+
+ALTER TABLE pieces
+    ADD COLUMN composer_id INT REFERENCES composers(composer_id);
+
+UPDATE pieces
+SET composer_id = composers.composer_id
+FROM composers
+WHERE composers.name = pieces.composer;
+
+ALTER TABLE pieces
+    ALTER COLUMN composer_id SET NOT NULL,
+    DROP CONSTRAINT pieces_title_composer_key,
+    DROP COLUMN composer,
+    ADD UNIQUE (title, composer_id);
+
+CREATE INDEX pieces_composer_id_idx ON pieces (composer_id);
@@ -0,0 +1,11 @@
+DROP INDEX pieces_parent_piece_id_title_key;
+DROP INDEX pieces_title_composer_id_key;
+
+ALTER TABLE pieces
+    DROP COLUMN movement_number,
+    DROP COLUMN parent_piece_id,
+    DROP COLUMN genre,
+    DROP COLUMN key,
+    DROP COLUMN instrumentation,
+    DROP COLUMN catalogue_number,
+    ADD UNIQUE (title, composer_id);
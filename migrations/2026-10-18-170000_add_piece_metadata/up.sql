@@ -0,0 +1,16 @@
+-- movements are pieces of their own with a parent work, so they can be practiced and
+-- tracked separately from the work as a whole
+ALTER TABLE pieces
+    ADD COLUMN catalogue_number VARCHAR(50), -- e.g. "BWV 1007", "Op. 27 No. 2"
+    ADD COLUMN key VARCHAR(40),
+    ADD COLUMN instrumentation VARCHAR(255),
+    ADD COLUMN genre VARCHAR(40),
+    ADD COLUMN parent_piece_id INT REFERENCES pieces(piece_id),
+    ADD COLUMN movement_number INT,
+    DROP CONSTRAINT pieces_title_composer_id_key;
+
+-- movement titles ("I. Prelude") only need to be unique within their work
+CREATE UNIQUE INDEX pieces_title_composer_id_key
+    ON pieces (title, composer_id) WHERE parent_piece_id IS NULL;
+CREATE UNIQUE INDEX pieces_parent_piece_id_title_key
+    ON pieces (parent_piece_id, title) WHERE parent_piece_id IS NOT NULL;
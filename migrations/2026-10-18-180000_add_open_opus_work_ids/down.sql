@@ -0,0 +1,2 @@
+ALTER TABLE pieces
+    DROP COLUMN open_opus_id;
@@ -0,0 +1,5 @@
+-- pieces loaded from Open Opus remember their work id, so reloading the catalogue updates
+-- them instead of adding them again
+ALTER TABLE pieces
+    ADD COLUMN open_opus_id INT,
+    ADD UNIQUE (open_opus_id);
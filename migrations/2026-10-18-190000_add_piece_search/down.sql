@@ -0,0 +1,2 @@
+DROP INDEX composers_name_fts_idx;
+DROP INDEX pieces_title_fts_idx;
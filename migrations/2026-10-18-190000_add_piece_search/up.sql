@@ -0,0 +1,8 @@
+-- full-text indexes for ranked piece search, over the same normalized text as the trigram
+-- indexes so "Dvorak" finds "Dvořák"; catalogue numbers are searched along with titles
+CREATE INDEX pieces_title_fts_idx ON pieces USING gin (
+    to_tsvector('english', normalize_text(title || ' ' || coalesce(catalogue_number, '')))
+);
+CREATE INDEX composers_name_fts_idx ON composers USING gin (
+    to_tsvector('english', normalize_text(name))
+);
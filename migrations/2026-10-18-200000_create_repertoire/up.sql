@@ -0,0 +1,14 @@
+-- the pieces each user is working on, independent of any particular practice session
+CREATE TABLE repertoire (
+    user_id INT NOT NULL REFERENCES users(user_id),
+    piece_id INT NOT NULL REFERENCES pieces(piece_id),
+    status VARCHAR(20) NOT NULL CHECK (
+        status IN ('wishlist', 'learning', 'polishing', 'performance_ready', 'retired')
+    ),
+    target_date DATE,
+    notes TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, piece_id)
+);
+
+CREATE INDEX repertoire_piece_id_idx ON repertoire (piece_id);
@@ -0,0 +1,16 @@
+-- targets like "300 minutes per week", "piece X 5 times a month" or "cello 4 days a week",
+-- measured over each day/week/month in the user's timezone
+CREATE TABLE practice_goals (
+    goal_id SERIAL NOT NULL,
+    user_id INT NOT NULL REFERENCES users(user_id),
+    metric VARCHAR(20) NOT NULL CHECK (metric IN ('minutes', 'sessions', 'days')),
+    period VARCHAR(20) NOT NULL CHECK (period IN ('day', 'week', 'month')),
+    target INT NOT NULL CHECK (target > 0),
+    piece_id INT REFERENCES pieces(piece_id), -- only count practice of this piece
+    instrument VARCHAR(20), -- only count sessions on this instrument
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (goal_id)
+);
+
+CREATE INDEX practice_goals_user_id_idx ON practice_goals (user_id);
+CREATE INDEX practice_goals_piece_id_idx ON practice_goals (piece_id);
@@ -0,0 +1,6 @@
+ALTER TABLE pieces_practiced
+    DROP COLUMN notes;
+
+ALTER TABLE practice_sessions
+    DROP COLUMN notes,
+    DROP COLUMN rating;
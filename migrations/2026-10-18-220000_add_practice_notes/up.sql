@@ -0,0 +1,12 @@
+ALTER TABLE practice_sessions
+    ADD COLUMN notes TEXT,
+    ADD COLUMN rating INT CHECK (rating BETWEEN 1 AND 5); -- how the session went, 1 to 5
+
+ALTER TABLE pieces_practiced
+    ADD COLUMN notes TEXT; -- what was worked on in the piece
+
+-- notes are searched by substring after normalization, which trigram indexes can serve
+CREATE INDEX practice_sessions_notes_trgm_idx
+    ON practice_sessions USING gin (normalize_text(notes) gin_trgm_ops);
+CREATE INDEX pieces_practiced_notes_trgm_idx
+    ON pieces_practiced USING gin (normalize_text(notes) gin_trgm_ops);
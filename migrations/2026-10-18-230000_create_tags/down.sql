@@ -0,0 +1,2 @@
+DROP TABLE practice_session_tags;
+DROP TABLE tags;
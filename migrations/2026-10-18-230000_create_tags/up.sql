@@ -0,0 +1,16 @@
+-- each user has their own tags, stored trimmed and lowercased so "Scales" and "scales" are one tag
+CREATE TABLE tags (
+    tag_id SERIAL NOT NULL,
+    user_id INT NOT NULL REFERENCES users(user_id),
+    name VARCHAR(50) NOT NULL,
+    UNIQUE (user_id, name),
+    PRIMARY KEY (tag_id)
+);
+
+CREATE TABLE practice_session_tags (
+    practice_session_id INT NOT NULL REFERENCES practice_sessions(practice_session_id),
+    tag_id INT NOT NULL REFERENCES tags(tag_id),
+    PRIMARY KEY (practice_session_id, tag_id)
+);
+
+CREATE INDEX practice_session_tags_tag_id_idx ON practice_session_tags (tag_id);
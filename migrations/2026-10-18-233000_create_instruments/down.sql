@@ -0,0 +1,24 @@
+ALTER TABLE practice_goals
+    ADD COLUMN instrument VARCHAR(20);
+
+UPDATE practice_goals
+SET instrument = instruments.name
+FROM instruments
+WHERE instruments.instrument_id = practice_goals.instrument_id;
+
+ALTER TABLE practice_goals
+    DROP COLUMN instrument_id;
+
+ALTER TABLE practice_sessions
+    ADD COLUMN instrument VARCHAR(20);
+
+UPDATE practice_sessions
+SET instrument = instruments.name
+FROM instruments
+WHERE instruments.instrument_id = practice_sessions.instrument_id;
+
+ALTER TABLE practice_sessions
+    ALTER COLUMN instrument SET NOT NULL,
+    DROP COLUMN instrument_id;
+
+DROP TABLE instruments;
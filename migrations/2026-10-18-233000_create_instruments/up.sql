@@ -0,0 +1,53 @@
+-- each user has their own instruments; names keep the user's spelling but are unique ignoring
+-- case, so "Violin" and "violin" are one instrument
+CREATE TABLE instruments (
+    instrument_id SERIAL NOT NULL,
+    user_id INT NOT NULL REFERENCES users(user_id),
+    name VARCHAR(20) NOT NULL,
+    family VARCHAR(20) CHECK (
+        family IN ('strings', 'woodwinds', 'brass', 'percussion', 'keyboard', 'voice', 'other')
+    ),
+    notes TEXT,
+    PRIMARY KEY (instrument_id)
+);
+
+CREATE UNIQUE INDEX instruments_user_id_name_key ON instruments (user_id, lower(name));
+
+-- every instrument a user has practiced or set a goal on becomes one of their instruments,
+-- spelled the way they used it most
+INSERT INTO instruments (user_id, name)
+SELECT DISTINCT ON (user_id, lower(name)) user_id, name
+FROM (
+    SELECT user_id, trim(instrument) AS name FROM practice_sessions
+    UNION ALL
+    SELECT user_id, trim(instrument) AS name FROM practice_goals WHERE instrument IS NOT NULL
+) used
+GROUP BY user_id, name
+ORDER BY user_id, lower(name), count(*) DESC, name;
+
+ALTER TABLE practice_sessions
+    ADD COLUMN instrument_id INT REFERENCES instruments(instrument_id);
+
+UPDATE practice_sessions
+SET instrument_id = instruments.instrument_id
+FROM instruments
+WHERE instruments.user_id = practice_sessions.user_id
+  AND lower(instruments.name) = lower(trim(practice_sessions.instrument));
+
+ALTER TABLE practice_sessions
+    ALTER COLUMN instrument_id SET NOT NULL,
+    DROP COLUMN instrument;
+
+CREATE INDEX practice_sessions_instrument_id_idx ON practice_sessions (instrument_id);
+
+ALTER TABLE practice_goals
+    ADD COLUMN instrument_id INT REFERENCES instruments(instrument_id); -- only count sessions on this instrument
+
+UPDATE practice_goals
+SET instrument_id = instruments.instrument_id
+FROM instruments
+WHERE instruments.user_id = practice_goals.user_id
+  AND lower(instruments.name) = lower(trim(practice_goals.instrument));
+
+ALTER TABLE practice_goals
+    DROP COLUMN instrument;
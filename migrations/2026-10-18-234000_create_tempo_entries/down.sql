@@ -0,0 +1 @@
+DROP TABLE tempo_entries;
@@ -0,0 +1,16 @@
+-- metronome tempos reached on a piece, or a passage of it, within a practice session
+CREATE TABLE tempo_entries (
+    tempo_entry_id SERIAL NOT NULL,
+    practice_session_id INT NOT NULL,
+    piece_id INT NOT NULL,
+    passage VARCHAR(100), -- e.g. "bars 32-48"; null for the piece as a whole
+    target_bpm INT CHECK (target_bpm > 0),
+    achieved_bpm INT NOT NULL CHECK (achieved_bpm > 0),
+    FOREIGN KEY (practice_session_id, piece_id)
+        REFERENCES pieces_practiced(practice_session_id, piece_id),
+    PRIMARY KEY (tempo_entry_id)
+);
+
+CREATE INDEX tempo_entries_practice_session_id_piece_id_idx
+    ON tempo_entries (practice_session_id, piece_id);
+CREATE INDEX tempo_entries_piece_id_idx ON tempo_entries (piece_id);
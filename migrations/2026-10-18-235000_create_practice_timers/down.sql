@@ -0,0 +1,2 @@
+DROP TABLE practice_timer_segments;
+DROP TABLE practice_timers;
@@ -0,0 +1,22 @@
+-- a practice session still in progress; each user has at most one. Time is measured in active
+-- seconds, which leave out time spent paused
+CREATE TABLE practice_timers (
+    user_id INT NOT NULL REFERENCES users(user_id),
+    instrument_id INT NOT NULL REFERENCES instruments(instrument_id),
+    started_at TIMESTAMPTZ NOT NULL,
+    paused_at TIMESTAMPTZ, -- set while the timer is paused
+    paused_secs INT NOT NULL DEFAULT 0 CHECK (paused_secs >= 0), -- time paused before paused_at
+    PRIMARY KEY (user_id)
+);
+
+-- the pieces switched to while a timer runs; each segment lasts until the next one starts
+CREATE TABLE practice_timer_segments (
+    segment_id SERIAL NOT NULL,
+    user_id INT NOT NULL REFERENCES practice_timers(user_id),
+    piece_id INT REFERENCES pieces(piece_id), -- null while practicing without a piece
+    start_secs INT NOT NULL CHECK (start_secs >= 0), -- active seconds into the timer
+    PRIMARY KEY (segment_id)
+);
+
+CREATE INDEX practice_timer_segments_user_id_idx ON practice_timer_segments (user_id);
+CREATE INDEX practice_timer_segments_piece_id_idx ON practice_timer_segments (piece_id);
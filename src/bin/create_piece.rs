@@ -1,7 +1,8 @@
 use diesel::prelude::*;
-use practice_app::establish_connection;
+use practice_app::composers::get_or_create_composer;
 use practice_app::models::{InsertablePiece, Piece};
 use practice_app::schema::pieces;
+use practice_app::{establish_connection, pieces_with_composers};
 use std::env;
 
 fn main() {
@@ -9,17 +10,38 @@ fn main() {
 
     args.next(); // skip first
 
-    let new_piece = InsertablePiece {
-        title: args.next().unwrap(),
-        composer: args.next().unwrap(),
-    };
+    let title = args.next().unwrap();
+    let composer_name = args.next().unwrap();
 
     let mut conn = establish_connection().unwrap();
 
-    let inserted_piece: Piece = diesel::insert_into(pieces::table)
+    let composer = match get_or_create_composer(&mut conn, &composer_name) {
+        Ok(composer) => composer,
+        Err(_) => panic!("Failed to find or create composer {composer_name}"),
+    };
+
+    let new_piece = InsertablePiece {
+        title,
+        composer_id: composer.composer_id,
+        created_by: None,
+        catalogue_number: None,
+        key: None,
+        instrumentation: None,
+        genre: None,
+        parent_piece_id: None,
+        movement_number: None,
+    };
+
+    let piece_id: i32 = diesel::insert_into(pieces::table)
         .values(new_piece)
+        .returning(pieces::piece_id)
         .get_result(&mut conn)
         .unwrap();
 
+    let inserted_piece: Piece = pieces_with_composers()
+        .filter(pieces::piece_id.eq(piece_id))
+        .first(&mut conn)
+        .unwrap();
+
     println!("Inserted: {inserted_piece}");
 }
@@ -12,6 +12,9 @@ fn main() {
     let mapping = PiecePracticedMapping {
         practice_session_id: args.next().unwrap().parse().unwrap(),
         piece_id: args.next().unwrap().parse().unwrap(),
+        duration_mins: args.next().map(|arg| arg.parse().unwrap()),
+        start_offset_mins: args.next().map(|arg| arg.parse().unwrap()),
+        notes: None,
     };
 
     let mut conn = establish_connection().unwrap();
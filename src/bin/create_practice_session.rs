@@ -1,6 +1,7 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use practice_app::establish_connection;
+use practice_app::instruments::get_or_create_instrument;
 use practice_app::models::{InsertablePracticeSession, PracticeSession};
 use practice_app::schema::practice_sessions;
 use std::env;
@@ -10,16 +11,28 @@ fn main() {
 
     args.next(); // skip first
 
-    let new_practice_session = InsertablePracticeSession {
-        start_datetime: NaiveDateTime::parse_from_str(&args.next().unwrap(), "%Y-%m-%dT%H:%M:%S")
-            .unwrap(),
-        duration_mins: args.next().unwrap().parse::<i32>().unwrap(),
-        instrument: args.next().unwrap(),
-        user_id: args.next().unwrap().parse::<i32>().unwrap(),
-    };
+    let start_datetime = DateTime::parse_from_rfc3339(&args.next().unwrap())
+        .unwrap()
+        .with_timezone(&Utc);
+    let duration_mins = args.next().unwrap().parse::<i32>().unwrap();
+    let instrument = args.next().unwrap();
+    let user_id = args.next().unwrap().parse::<i32>().unwrap();
 
     let mut conn = establish_connection().unwrap();
 
+    let Ok(instrument) = get_or_create_instrument(&mut conn, user_id, &instrument) else {
+        panic!("Invalid instrument: {instrument}");
+    };
+
+    let new_practice_session = InsertablePracticeSession {
+        start_datetime,
+        duration_mins,
+        user_id,
+        notes: None,
+        rating: None,
+        instrument_id: instrument.instrument_id,
+    };
+
     let inserted_practice_session: PracticeSession = diesel::insert_into(practice_sessions::table)
         .values(new_practice_session)
         .get_result(&mut conn)
@@ -1,4 +1,3 @@
-use argon2;
 use diesel::prelude::*;
 use practice_app::establish_connection;
 use practice_app::models::User;
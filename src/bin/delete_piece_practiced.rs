@@ -1,6 +1,6 @@
 use diesel::{delete, prelude::*};
 use practice_app::establish_connection;
-use practice_app::schema::pieces_practiced;
+use practice_app::schema::{pieces_practiced, tempo_entries};
 use std::env;
 
 fn main() {
@@ -14,6 +14,15 @@ fn main() {
 
     let mut conn = establish_connection().unwrap();
 
+    // tempo entries belong to the mapping, so they go with it
+    delete(
+        tempo_entries::table
+            .filter(tempo_entries::practice_session_id.eq(practice_session_id))
+            .filter(tempo_entries::piece_id.eq(piece_id)),
+    )
+    .execute(&mut conn)
+    .unwrap();
+
     let num_deleted = delete(
         pieces_practiced::table
             .filter(pieces_practiced::practice_session_id.eq(practice_session_id))
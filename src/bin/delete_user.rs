@@ -1,6 +1,4 @@
-use diesel::{delete, prelude::*};
-use practice_app::establish_connection;
-use practice_app::schema::users;
+use practice_app::{delete_user, establish_connection};
 use std::env;
 
 fn main() {
@@ -13,9 +11,10 @@ fn main() {
 
     let mut conn = establish_connection().unwrap();
 
-    let num_deleted = delete(users::table.filter(users::user_id.eq(user_id)))
-        .execute(&mut conn)
-        .unwrap();
+    let num_deleted = match delete_user(&mut conn, user_id) {
+        Ok(num_deleted) => num_deleted,
+        Err(_) => panic!("Failed to delete user {user_id}"),
+    };
 
     println!("Users: {num_deleted}");
 }
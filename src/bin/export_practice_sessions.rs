@@ -0,0 +1,44 @@
+use chrono::NaiveDateTime;
+use practice_app::export::{write_practice_sessions, ExportFormat};
+use practice_app::{establish_connection, get_user_timezone, PracticeSessionsQueryParams};
+use std::env;
+use std::io;
+
+// usage: export_practice_sessions <user_id> <csv|json_lines|ics> [min_datetime] [max_datetime]
+// datetimes are given as %Y-%m-%dT%H:%M:%S in the user's timezone
+fn main() {
+    let mut args = env::args();
+
+    args.next(); // skip first
+
+    let user_id: i32 = args.next().unwrap().parse().unwrap();
+
+    let format = match args.next().unwrap().as_str() {
+        "csv" => ExportFormat::Csv,
+        "json_lines" => ExportFormat::JsonLines,
+        "ics" => ExportFormat::Ics,
+        other => panic!("Unknown export format: {other}"),
+    };
+
+    let parse_datetime =
+        |arg: String| NaiveDateTime::parse_from_str(&arg, "%Y-%m-%dT%H:%M:%S").unwrap();
+
+    let filters = PracticeSessionsQueryParams {
+        min_datetime: args.next().map(parse_datetime),
+        max_datetime: args.next().map(parse_datetime),
+        ..Default::default()
+    };
+
+    let mut conn = establish_connection().unwrap();
+
+    let timezone = get_user_timezone(&mut conn, user_id)
+        .unwrap_or_else(|_| panic!("Could not load timezone for user {user_id}"));
+
+    let mut stdout = io::stdout().lock();
+
+    if write_practice_sessions(&mut conn, user_id, &timezone, &filters, format, &mut stdout)
+        .is_err()
+    {
+        panic!("Failed to export practice sessions");
+    }
+}
@@ -1,16 +1,31 @@
-use diesel::*;
-use practice_app::{establish_connection, models::InsertablePiece, schema::pieces};
-use reqwest;
+use diesel::prelude::*;
+use practice_app::composers::make_sort_name;
+use practice_app::models::{Composer, InsertableComposer};
+use practice_app::schema::{composers, pieces};
+use practice_app::{establish_connection, normalize_text};
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+
+const DUMP_URL: &str = "https://api.openopus.org/work/dump.json";
 
 #[derive(Deserialize)]
 struct WorkJson {
+    id: Option<String>,
     title: String,
+    genre: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct ComposerJson {
+    id: String,
     complete_name: String,
+    epoch: Option<String>,
+    birth: Option<String>, // yyyy-mm-dd
+    death: Option<String>,
     popular: String,
     works: Vec<WorkJson>,
 }
@@ -20,47 +35,273 @@ struct ResponseJson {
     composers: Vec<ComposerJson>,
 }
 
-#[tokio::main]
-async fn main() {
-    let mut conn = establish_connection().unwrap();
-
-    let url = "https://api.openopus.org/work/dump.json";
-
-    let body = reqwest::get(url)
-        .await
-        .unwrap()
-        .json::<ResponseJson>()
-        .await
-        .unwrap();
-
-    let pieces: Vec<InsertablePiece> = body
-        .composers
-        .into_iter()
-        .filter(|composer| composer.popular == "1")
-        .map(|composer| {
-            composer
-                .works
-                .into_iter()
-                .map(|work| InsertablePiece {
-                    composer: composer.complete_name.clone(),
-                    title: work.title,
-                })
-                .collect::<Vec<InsertablePiece>>()
-        })
-        .flatten()
-        .collect();
+#[derive(Insertable)]
+#[diesel(table_name = pieces)]
+struct OpenOpusPiece {
+    title: String,
+    composer_id: i32,
+    genre: Option<String>,
+    open_opus_id: Option<i32>,
+}
+
+#[derive(Default)]
+struct Counts {
+    added: usize,
+    updated: usize,
+    skipped: usize, // already up to date, or repeated within the dump
+}
+
+impl Counts {
+    fn print(&self, name: &str) {
+        println!(
+            "{name}: {} added, {} updated, {} skipped",
+            self.added, self.updated, self.skipped
+        );
+    }
+}
+
+fn parse_year(date: &Option<String>) -> Option<i32> {
+    date.as_deref()?.get(..4)?.parse().ok()
+}
+
+fn non_empty(field: Option<String>) -> Option<String> {
+    field
+        .map(|field| field.trim().to_owned())
+        .filter(|field| !field.is_empty())
+}
+
+// composers are matched by their Open Opus id, then by name (ignoring case, accents and
+// punctuation) so composers users already added get linked instead of duplicated; returns
+// None for composers that are left out
+fn upsert_composer(
+    conn: &mut PgConnection,
+    composer: &ComposerJson,
+    counts: &mut Counts,
+) -> QueryResult<Option<i32>> {
+    let open_opus_id: Option<i32> = composer.id.parse().ok();
+
+    let existing_composer: Option<Composer> = match open_opus_id {
+        Some(open_opus_id) => composers::table
+            .filter(composers::open_opus_id.eq(open_opus_id))
+            .first::<Composer>(conn)
+            .optional()?,
+        None => None,
+    };
+
+    let existing_composer = match existing_composer {
+        Some(existing_composer) => Some(existing_composer),
+        None => composers::table
+            .filter(normalize_text(composers::name).eq(normalize_text(&composer.complete_name)))
+            .order(composers::composer_id)
+            .first::<Composer>(conn)
+            .optional()?,
+    };
+
+    // a composer of the same name already linked to a different Open Opus composer can't take
+    // this id as well, and names are unique, so this composer and its works are left out
+    if existing_composer.as_ref().is_some_and(|existing_composer| {
+        existing_composer.open_opus_id.is_some() && existing_composer.open_opus_id != open_opus_id
+    }) {
+        counts.skipped += 1;
+        return Ok(None);
+    }
+
+    let birth_year = parse_year(&composer.birth);
+    let death_year = parse_year(&composer.death);
+    let era = non_empty(composer.epoch.clone());
+
+    let Some(existing_composer) = existing_composer else {
+        counts.added += 1;
+        return diesel::insert_into(composers::table)
+            .values(InsertableComposer {
+                name: composer.complete_name.clone(),
+                sort_name: make_sort_name(&composer.complete_name),
+                birth_year,
+                death_year,
+                era,
+                open_opus_id,
+            })
+            .returning(composers::composer_id)
+            .get_result(conn)
+            .map(Some);
+    };
+
+    // names are left alone, since users may have chosen them
+    if (
+        existing_composer.birth_year,
+        existing_composer.death_year,
+        &existing_composer.era,
+        existing_composer.open_opus_id,
+    ) == (birth_year, death_year, &era, open_opus_id)
+    {
+        counts.skipped += 1;
+    } else {
+        diesel::update(composers::table.find(existing_composer.composer_id))
+            .set((
+                composers::birth_year.eq(birth_year),
+                composers::death_year.eq(death_year),
+                composers::era.eq(era),
+                composers::open_opus_id.eq(open_opus_id),
+            ))
+            .execute(conn)?;
+        counts.updated += 1;
+    }
+
+    Ok(Some(existing_composer.composer_id))
+}
+
+// works are matched by their Open Opus id, then by title among the composer's pieces that
+// aren't linked to Open Opus yet; matched pieces take the Open Opus title and genre
+fn upsert_works(
+    conn: &mut PgConnection,
+    composer_id: i32,
+    works: Vec<WorkJson>,
+    counts: &mut Counts,
+) -> QueryResult<()> {
+    let existing_pieces: Vec<(i32, String, Option<String>, Option<i32>)> = pieces::table
+        .select((
+            pieces::piece_id,
+            pieces::title,
+            pieces::genre,
+            pieces::open_opus_id,
+        ))
+        .filter(pieces::composer_id.eq(composer_id))
+        .filter(pieces::parent_piece_id.is_null())
+        .load(conn)?;
 
-    let non_duplicates = pieces
+    let by_open_opus_id: HashMap<i32, usize> = existing_pieces
         .iter()
         .enumerate()
-        .filter(|(index, piece)| *index == pieces.iter().position(|x| x == *piece).unwrap())
-        .map(|(_, piece)| piece)
-        .collect::<Vec<&InsertablePiece>>();
+        .filter_map(|(i, piece)| Some((piece.3?, i)))
+        .collect();
+    let by_title: HashMap<String, usize> = existing_pieces
+        .iter()
+        .enumerate()
+        .filter(|(_, piece)| piece.3.is_none())
+        .map(|(i, piece)| (piece.1.to_lowercase(), i))
+        .collect();
+
+    let mut seen_titles: HashSet<String> = HashSet::new();
+    let mut new_pieces: Vec<OpenOpusPiece> = Vec::new();
+
+    for work in works {
+        let title = work.title.trim().to_owned();
+        if title.is_empty() || !seen_titles.insert(title.to_lowercase()) {
+            counts.skipped += 1;
+            continue;
+        }
+
+        let open_opus_id: Option<i32> = work.id.as_deref().and_then(|id| id.parse().ok());
+        let genre = non_empty(work.genre);
+
+        let existing_piece = open_opus_id
+            .and_then(|id| by_open_opus_id.get(&id))
+            .or_else(|| by_title.get(&title.to_lowercase()))
+            .map(|i| &existing_pieces[*i]);
+
+        let Some((piece_id, existing_title, existing_genre, existing_open_opus_id)) =
+            existing_piece
+        else {
+            new_pieces.push(OpenOpusPiece {
+                title,
+                composer_id,
+                genre,
+                open_opus_id,
+            });
+            continue;
+        };
+
+        // a genre set by hand is kept for pieces that weren't loaded from Open Opus
+        let genre = match existing_open_opus_id {
+            Some(_) => genre,
+            None => existing_genre.clone().or(genre),
+        };
+
+        if (existing_title, existing_genre, *existing_open_opus_id)
+            == (&title, &genre, open_opus_id)
+        {
+            counts.skipped += 1;
+            continue;
+        }
+
+        // renaming onto the title of another of the composer's pieces would break uniqueness
+        let title_taken: bool = diesel::select(diesel::dsl::exists(
+            pieces::table
+                .filter(pieces::composer_id.eq(composer_id))
+                .filter(pieces::parent_piece_id.is_null())
+                .filter(pieces::piece_id.ne(piece_id))
+                .filter(pieces::title.eq(&title)),
+        ))
+        .get_result(conn)?;
+        if title_taken {
+            counts.skipped += 1;
+            continue;
+        }
+
+        diesel::update(pieces::table.find(piece_id))
+            .set((
+                pieces::title.eq(&title),
+                pieces::genre.eq(&genre),
+                pieces::open_opus_id.eq(open_opus_id),
+            ))
+            .execute(conn)?;
+        counts.updated += 1;
+    }
+
+    let rows_inserted = diesel::insert_into(pieces::table)
+        .values(&new_pieces)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    counts.added += rows_inserted;
+    counts.skipped += new_pieces.len() - rows_inserted;
+
+    Ok(())
+}
+
+// usage: load_pieces [--all-composers] [dump_path]
+// loads the Open Opus catalogue from a dump file, or downloads it if no path is given. Only
+// popular composers are loaded unless --all-composers is passed. Loading is idempotent, so
+// re-running it only picks up what changed
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut all_composers = false;
+    let mut dump_path: Option<String> = None;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--all-composers" => all_composers = true,
+            flag if flag.starts_with("--") => return Err(format!("Unknown flag: {flag}").into()),
+            _ => dump_path = Some(arg),
+        }
+    }
+
+    let body: ResponseJson = match dump_path {
+        Some(dump_path) => serde_json::from_reader(BufReader::new(File::open(dump_path)?))?,
+        None => reqwest::get(DUMP_URL).await?.json().await?,
+    };
+
+    let mut conn = establish_connection()?;
+
+    let mut composer_counts = Counts::default();
+    let mut piece_counts = Counts::default();
+
+    // everything is loaded in one transaction, so a failure part way leaves nothing behind
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        for composer in body
+            .composers
+            .into_iter()
+            .filter(|composer| all_composers || composer.popular == "1")
+        {
+            if let Some(composer_id) = upsert_composer(conn, &composer, &mut composer_counts)? {
+                upsert_works(conn, composer_id, composer.works, &mut piece_counts)?;
+            }
+        }
+        Ok(())
+    })?;
 
-    let res = diesel::insert_into(pieces::table)
-        .values(non_duplicates)
-        .execute(&mut conn)
-        .unwrap();
+    composer_counts.print("Composers");
+    piece_counts.print("Pieces");
 
-    println!("{res} rows affected");
+    Ok(())
 }
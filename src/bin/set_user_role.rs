@@ -0,0 +1,30 @@
+use diesel::prelude::*;
+use practice_app::establish_connection;
+use practice_app::models::{Role, User};
+use practice_app::schema::users;
+use std::env;
+
+// usage: set_user_role <user_id> <user|admin>
+// this is how the first admin gets created, since only admins can change roles over the api
+fn main() {
+    let mut args = env::args();
+
+    args.next(); // skip first
+
+    let user_id: i32 = args.next().unwrap().parse().unwrap();
+
+    let role = match args.next().unwrap().as_str() {
+        "user" => Role::User,
+        "admin" => Role::Admin,
+        other => panic!("Unknown role: {other}"),
+    };
+
+    let mut conn = establish_connection().unwrap();
+
+    let updated_user: User = diesel::update(users::table.filter(users::user_id.eq(user_id)))
+        .set(users::role.eq(role))
+        .get_result(&mut conn)
+        .unwrap();
+
+    println!("Updated user: {updated_user}");
+}
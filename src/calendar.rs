@@ -0,0 +1,402 @@
+use crate::import::{check_conflicts, resolve_row, ImportRow, ImportRowError};
+use crate::{local_to_utc, AppError, PracticeSessionWithPieces};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use rand::Rng;
+
+const PRODUCT_ID: &str = "-//practice_app//Practice Sessions//EN";
+const CALENDAR_NAME: &str = "Practice sessions";
+const SUMMARY_PREFIX: &str = "Practice: ";
+const UTC_DATETIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const LOCAL_DATETIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+const DATE_FORMAT: &str = "%Y%m%d";
+const MAX_LINE_OCTETS: usize = 75;
+const CALENDAR_TOKEN_BYTES: usize = 32;
+
+// the secret in a user's calendar feed url, which calendar apps fetch without a session cookie
+pub fn generate_calendar_token() -> String {
+    URL_SAFE_NO_PAD.encode(rand::thread_rng().gen::<[u8; CALENDAR_TOKEN_BYTES]>())
+}
+
+// escapes a TEXT value (RFC 5545 section 3.3.11)
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            },
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+// writes a content line, folding it so no line is longer than 75 octets (RFC 5545 section 3.1)
+fn push_line(out: &mut String, line: &str) {
+    let mut line_octets = 0;
+    for c in line.chars() {
+        if line_octets + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            line_octets = 1;
+        }
+        out.push(c);
+        line_octets += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+pub fn calendar_header() -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, &format!("PRODID:{PRODUCT_ID}"));
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, "METHOD:PUBLISH");
+    push_line(&mut out, &format!("X-WR-CALNAME:{CALENDAR_NAME}"));
+    out
+}
+
+pub fn calendar_footer() -> String {
+    let mut out = String::new();
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+// one VEVENT per practice session, with the pieces practiced in the description, one
+// "Composer: Title" per line
+pub fn encode_events(
+    practice_sessions: &[PracticeSessionWithPieces],
+    timestamp: DateTime<Utc>,
+) -> String {
+    let mut out = String::new();
+    for practice_session in practice_sessions {
+        let end_datetime = practice_session.start_datetime
+            + Duration::minutes(practice_session.duration_mins as i64);
+
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(
+            &mut out,
+            &format!(
+                "UID:practice-session-{}@practice_app",
+                practice_session.practice_session_id
+            ),
+        );
+        push_line(
+            &mut out,
+            &format!("DTSTAMP:{}", timestamp.format(UTC_DATETIME_FORMAT)),
+        );
+        push_line(
+            &mut out,
+            &format!(
+                "DTSTART:{}",
+                practice_session.start_datetime.format(UTC_DATETIME_FORMAT)
+            ),
+        );
+        push_line(
+            &mut out,
+            &format!("DTEND:{}", end_datetime.format(UTC_DATETIME_FORMAT)),
+        );
+        push_line(
+            &mut out,
+            &format!(
+                "SUMMARY:{}",
+                escape_text(&format!("{SUMMARY_PREFIX}{}", practice_session.instrument))
+            ),
+        );
+        if !practice_session.pieces_practiced.is_empty() {
+            let description = practice_session
+                .pieces_practiced
+                .iter()
+                .map(|piece_practiced| {
+                    format!(
+                        "{}: {}",
+                        piece_practiced.piece.composer, piece_practiced.piece.title
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+            push_line(
+                &mut out,
+                &format!("DESCRIPTION:{}", escape_text(&description)),
+            );
+        }
+        push_line(&mut out, "END:VEVENT");
+    }
+    out
+}
+
+struct ContentLine {
+    line: u64,
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl ContentLine {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param_name, _)| param_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// joins folded lines back together, keeping the line number each one started on
+fn unfold(ics_data: &str) -> Vec<(u64, String)> {
+    let mut lines: Vec<(u64, String)> = Vec::new();
+    for (index, line) in ics_data.lines().enumerate() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, previous))) => previous.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push((index as u64 + 1, line.to_owned())),
+        }
+    }
+    lines
+}
+
+// splits "NAME;PARAM=value:value" on the separators that aren't inside quoted parameter values
+fn parse_content_line(line: u64, content: &str) -> Option<ContentLine> {
+    let mut in_quotes = false;
+    let mut parts: Vec<String> = vec![String::new()];
+    let mut value: Option<String> = None;
+
+    for (index, c) in content.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => parts.push(String::new()),
+            ':' if !in_quotes => {
+                value = Some(content[index + 1..].to_owned());
+                break;
+            }
+            c => parts.last_mut()?.push(c),
+        }
+    }
+
+    let mut parts = parts.into_iter();
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|param| {
+            param
+                .split_once('=')
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        })
+        .collect();
+
+    Some(ContentLine {
+        line,
+        name,
+        params,
+        value: value?,
+    })
+}
+
+// DATE values are midnight in the event's timezone; DATE-TIME values are utc when they end in
+// "Z", otherwise in the TZID given or else the user's timezone
+fn parse_datetime(content_line: &ContentLine, timezone: &Tz) -> Result<DateTime<Utc>, String> {
+    let value = content_line.value.trim();
+    let timezone = match content_line.param("TZID") {
+        Some(tzid) => tzid
+            .parse::<Tz>()
+            .map_err(|_| format!("Unknown timezone: {tzid}"))?,
+        None => *timezone,
+    };
+
+    let local_datetime = if let Some(utc_value) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc_value, LOCAL_DATETIME_FORMAT)
+            .map(|datetime| DateTime::from_utc(datetime, Utc))
+            .map_err(|_| format!("Invalid {}: {value}", content_line.name));
+    } else if let Ok(datetime) = NaiveDateTime::parse_from_str(value, LOCAL_DATETIME_FORMAT) {
+        datetime
+    } else {
+        NaiveDate::parse_from_str(value, DATE_FORMAT)
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .ok_or(format!("Invalid {}: {value}", content_line.name))?
+    };
+
+    local_to_utc(local_datetime, &timezone)
+        .map_err(|_| format!("{value} does not exist in timezone {timezone}"))
+}
+
+// parses a DURATION value (RFC 5545 section 3.3.6) such as "PT1H30M" into whole minutes
+fn parse_duration_mins(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let value = value.strip_prefix('+').unwrap_or(value);
+
+    let mut seconds: i64 = 0;
+    let mut in_time = false;
+    let mut number = String::new();
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => number.push(c),
+            unit => {
+                let unit_seconds = match (unit, in_time) {
+                    ('W', false) => 60 * 60 * 24 * 7,
+                    ('D', false) => 60 * 60 * 24,
+                    ('H', true) => 60 * 60,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+                // absurdly long durations are invalid rather than overflowing
+                seconds =
+                    seconds.checked_add(number.parse::<i64>().ok()?.checked_mul(unit_seconds)?)?;
+                number.clear();
+            }
+        }
+    }
+
+    if !number.is_empty() {
+        return None;
+    }
+
+    Some(seconds / 60)
+}
+
+fn parse_event(
+    conn: &mut PgConnection,
+    row: u64,
+    properties: &[ContentLine],
+    timezone: &Tz,
+    instrument: Option<&str>,
+) -> Result<Result<ImportRow, String>, AppError> {
+    let property = |name: &str| properties.iter().find(|property| property.name == name);
+
+    if property("RRULE").is_some() || property("RDATE").is_some() {
+        return Ok(Err("Recurring events are not supported".to_owned()));
+    }
+
+    let start_datetime = match property("DTSTART").map(|dtstart| parse_datetime(dtstart, timezone))
+    {
+        Some(Ok(start_datetime)) => start_datetime,
+        Some(Err(e)) => return Ok(Err(e)),
+        None => return Ok(Err("Missing DTSTART".to_owned())),
+    };
+
+    let duration_mins = match (property("DTEND"), property("DURATION")) {
+        (Some(dtend), _) => match parse_datetime(dtend, timezone) {
+            Ok(end_datetime) => (end_datetime - start_datetime).num_minutes(),
+            Err(e) => return Ok(Err(e)),
+        },
+        (None, Some(duration)) => match parse_duration_mins(&duration.value) {
+            Some(duration_mins) => duration_mins,
+            None => return Ok(Err(format!("Invalid DURATION: {}", duration.value))),
+        },
+        (None, None) => return Ok(Err("Missing DTEND or DURATION".to_owned())),
+    };
+    let duration_mins = match u32::try_from(duration_mins) {
+        Ok(duration_mins) => duration_mins,
+        Err(_) if duration_mins < 0 => return Ok(Err("Event ends before it starts".to_owned())),
+        Err(_) => return Ok(Err("Event is too long".to_owned())),
+    };
+
+    // events exported from here are titled "Practice: <instrument>"
+    let summary = property("SUMMARY")
+        .map(|summary| unescape_text(&summary.value))
+        .unwrap_or_default();
+    let summary = summary.trim();
+    let instrument = instrument.unwrap_or(summary.strip_prefix(SUMMARY_PREFIX).unwrap_or(summary));
+
+    let description = property("DESCRIPTION")
+        .map(|description| unescape_text(&description.value))
+        .unwrap_or_default();
+
+    resolve_row(
+        conn,
+        row,
+        start_datetime,
+        duration_mins,
+        instrument,
+        description.lines(),
+    )
+}
+
+// parses and validates every VEVENT in an iCalendar file, splitting them into practice sessions
+// that can be imported and errors; rows are the line numbers the events begin on. The instrument
+// is taken from each event's summary unless one is given for all of them
+pub fn parse_practice_sessions_ics(
+    conn: &mut PgConnection,
+    user_id: i32,
+    timezone: &Tz,
+    ics_data: &str,
+    instrument: Option<&str>,
+) -> Result<(Vec<ImportRow>, Vec<ImportRowError>), AppError> {
+    let mut content_lines = Vec::new();
+    for (line, content) in unfold(ics_data) {
+        content_lines.push(
+            parse_content_line(line, &content).ok_or(AppError::ClientError(format!(
+                "Invalid calendar: malformed line {line}"
+            )))?,
+        );
+    }
+
+    match content_lines.first() {
+        Some(first) if first.name == "BEGIN" && first.value.eq_ignore_ascii_case("VCALENDAR") => {}
+        _ => {
+            return Err(AppError::ClientError(
+                "Invalid calendar: missing BEGIN:VCALENDAR".to_owned(),
+            ))
+        }
+    }
+
+    // properties are only collected directly inside a VEVENT, which skips VTIMEZONE and VALARM
+    // components and the properties of the calendar itself
+    let mut components: Vec<String> = Vec::new();
+    let mut event: Option<(u64, Vec<ContentLine>)> = None;
+    let mut parsed_rows: Vec<(u64, Result<ImportRow, String>)> = Vec::new();
+
+    for content_line in content_lines {
+        match content_line.name.as_str() {
+            "BEGIN" => {
+                let component = content_line.value.trim().to_ascii_uppercase();
+                if component == "VEVENT" {
+                    event = Some((content_line.line, Vec::new()));
+                }
+                components.push(component);
+            }
+            "END" => {
+                let component = components.pop();
+                if let (Some("VEVENT"), Some((row, properties))) =
+                    (component.as_deref(), event.as_ref())
+                {
+                    parsed_rows.push((
+                        *row,
+                        parse_event(conn, *row, properties, timezone, instrument)?,
+                    ));
+                    event = None;
+                }
+            }
+            _ if components.last().map(String::as_str) == Some("VEVENT") => {
+                if let Some((_, properties)) = event.as_mut() {
+                    properties.push(content_line);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    check_conflicts(conn, user_id, parsed_rows)
+}
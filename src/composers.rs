@@ -0,0 +1,116 @@
+use crate::models::Composer;
+use crate::schema::composers;
+use crate::{escape_like, map_backend_err, normalize_text, AppError};
+use diesel::dsl::sql;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Float4, Text};
+use diesel::upsert::excluded;
+use serde::{Deserialize, Serialize};
+
+// "Johann Sebastian Bach" sorts as "Bach, Johann Sebastian"
+pub fn make_sort_name(name: &str) -> String {
+    match name.trim().rsplit_once(char::is_whitespace) {
+        Some((given_names, surname)) => format!("{}, {}", surname, given_names.trim_end()),
+        None => name.trim().to_owned(),
+    }
+}
+
+// finds the composer with this name, ignoring case, accents and punctuation so "J.S. Bach"
+// matches "J. S. Bach", and creates it if there isn't one
+pub fn normalize_composer_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::ClientError("Missing composer".to_owned()));
+    }
+
+    Ok(name)
+}
+
+pub fn get_or_create_composer(conn: &mut PgConnection, name: &str) -> Result<Composer, AppError> {
+    let name = normalize_composer_name(name)?;
+
+    let existing_composer: Option<Composer> = map_backend_err!(composers::table
+        .filter(normalize_text(composers::name).eq(normalize_text(name)))
+        .order(composers::composer_id)
+        .first::<Composer>(conn)
+        .optional())?;
+
+    if let Some(composer) = existing_composer {
+        return Ok(composer);
+    }
+
+    // another request may have created the composer since it was looked up
+    map_backend_err!(diesel::insert_into(composers::table)
+        .values((
+            composers::name.eq(name),
+            composers::sort_name.eq(make_sort_name(name)),
+        ))
+        .on_conflict(composers::name)
+        .do_update()
+        .set(composers::name.eq(excluded(composers::name)))
+        .get_result::<Composer>(conn))
+}
+
+#[derive(Deserialize)]
+pub struct ComposersQueryParams {
+    pub composer_id: Option<i32>,
+    pub name: Option<String>, // match containing
+    pub era: Option<String>,  // exact match, ignoring case
+    pub open_opus_id: Option<i32>,
+}
+
+impl ComposersQueryParams {
+    // the composers matching every filter, before sorting and pagination
+    pub fn filtered_query(&self) -> composers::BoxedQuery<'static, Pg> {
+        let mut query = composers::table.into_boxed();
+
+        if let Some(composer_id) = self.composer_id {
+            query = query.filter(composers::composer_id.eq(composer_id));
+        }
+
+        if let Some(name) = &self.name {
+            query = query.filter(composers::name.ilike(format!("%{}%", name)));
+        }
+
+        if let Some(era) = &self.era {
+            query = query.filter(composers::era.ilike(escape_like(era)));
+        }
+
+        if let Some(open_opus_id) = self.open_opus_id {
+            query = query.filter(composers::open_opus_id.eq(open_opus_id));
+        }
+
+        query
+    }
+}
+
+#[derive(Queryable, Serialize)]
+pub struct ComposerMatch {
+    #[serde(flatten)]
+    pub composer: Composer,
+    pub score: f32,
+}
+
+// composers ranked by how closely their name matches the search, allowing for typos and
+// partial names ("bach", "dvorak", "tchaikovsky" for "Tchaikovsky, Pyotr Ilyich")
+pub fn search_composers(
+    conn: &mut PgConnection,
+    search: &str,
+    limit: i64,
+) -> Result<Vec<ComposerMatch>, AppError> {
+    let score = sql::<Float4>("word_similarity(normalize_text(")
+        .bind::<Text, _>(search.to_owned())
+        .sql("), normalize_text(name))");
+
+    map_backend_err!(composers::table
+        .select((Composer::as_select(), score.clone()))
+        .filter(
+            sql::<diesel::sql_types::Bool>("normalize_text(")
+                .bind::<Text, _>(search.to_owned())
+                .sql(") <% normalize_text(name)"),
+        )
+        .order((score.desc(), composers::sort_name))
+        .limit(limit)
+        .load::<ComposerMatch>(conn))
+}
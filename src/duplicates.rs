@@ -0,0 +1,406 @@
+use crate::models::{Piece, PiecePracticedMapping, TempoEntry};
+use crate::schema::{
+    pieces, pieces_practiced, practice_goals, practice_sessions, practice_timer_segments,
+    repertoire, tempo_entries,
+};
+use crate::{map_backend_err, pieces_with_composers, AppError};
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
+use diesel::sql_types::{BigInt, Double, Float4, Integer, Nullable, Text, Varchar};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+// a pair of pieces that may be the same work, with how alike their titles and composers are
+#[derive(QueryableByName, Serialize)]
+pub struct DuplicateCandidate {
+    #[diesel(sql_type = Integer)]
+    pub piece_id: i32,
+    #[diesel(sql_type = Varchar)]
+    pub title: String,
+    #[diesel(sql_type = Varchar)]
+    pub composer: String,
+    #[diesel(sql_type = Integer)]
+    pub duplicate_piece_id: i32,
+    #[diesel(sql_type = Varchar)]
+    pub duplicate_title: String,
+    #[diesel(sql_type = Varchar)]
+    pub duplicate_composer: String,
+    #[diesel(sql_type = Float4)]
+    pub similarity: f32,
+}
+
+// pairs of pieces whose normalized titles are trigram-similar, scored by averaging title
+// similarity with composer similarity; composers are compared word-wise so "Bach" still
+// matches "Johann Sebastian Bach". When a piece id is given, only pairs including it are found
+pub fn find_duplicate_pieces(
+    conn: &mut PgConnection,
+    piece_id: Option<i32>,
+    min_similarity: f64,
+    limit: i64,
+) -> Result<Vec<DuplicateCandidate>, AppError> {
+    conn.transaction::<_, AppError, _>(|conn| {
+        // the % operator uses this threshold, which lets it narrow candidates with the index
+        diesel::sql_query("SELECT set_config('pg_trgm.similarity_threshold', $1, true)")
+            .bind::<Text, _>(min_similarity.to_string())
+            .execute(conn)?;
+
+        Ok(diesel::sql_query(
+            "SELECT * FROM ( \
+                 SELECT a.piece_id, a.title, ac.name AS composer, \
+                        b.piece_id AS duplicate_piece_id, \
+                        b.title AS duplicate_title, \
+                        bc.name AS duplicate_composer, \
+                        ((similarity(normalize_text(a.title), normalize_text(b.title)) \
+                          + CASE WHEN a.composer_id = b.composer_id THEN 1 ELSE greatest( \
+                              word_similarity(normalize_text(ac.name), normalize_text(bc.name)), \
+                              word_similarity(normalize_text(bc.name), normalize_text(ac.name)) \
+                          ) END) / 2)::REAL AS similarity \
+                 FROM pieces a \
+                 JOIN composers ac ON ac.composer_id = a.composer_id \
+                 JOIN pieces b \
+                   ON b.piece_id > a.piece_id \
+                  AND normalize_text(a.title) % normalize_text(b.title) \
+                 JOIN composers bc ON bc.composer_id = b.composer_id \
+                 WHERE ($1 IS NULL OR a.piece_id = $1 OR b.piece_id = $1) \
+             ) candidates \
+             WHERE similarity >= $2 \
+             ORDER BY similarity DESC, piece_id, duplicate_piece_id \
+             LIMIT $3",
+        )
+        .bind::<Nullable<Integer>, _>(piece_id)
+        .bind::<Double, _>(min_similarity)
+        .bind::<BigInt, _>(limit)
+        .load::<DuplicateCandidate>(conn)?)
+    })
+}
+
+#[derive(Serialize)]
+pub struct MergeSummary {
+    pub target: Piece,
+    pub pieces_merged: usize,
+    pub mappings_repointed: usize,
+    pub mappings_combined: usize, // sessions that already had more than one of the pieces
+    pub tempo_entries_repointed: usize,
+    pub movements_repointed: usize,
+    pub repertoire_entries_repointed: usize,
+    pub goals_repointed: usize,
+}
+
+// the single mapping a session keeps when several of the merged pieces were practiced in it:
+// the time allocated to each is added up, starting at the earliest offset (moved earlier if
+// needed so the allocation still ends within the session), and their notes are kept together
+fn combine_mappings(
+    practice_session_id: i32,
+    target_piece_id: i32,
+    mappings: &[PiecePracticedMapping],
+    session_duration_mins: i32,
+) -> PiecePracticedMapping {
+    let duration_mins = mappings
+        .iter()
+        .filter_map(|mapping| mapping.duration_mins)
+        .reduce(|a, b| a + b);
+    let start_offset_mins = mappings
+        .iter()
+        .filter_map(|mapping| mapping.start_offset_mins)
+        .min()
+        .map(|offset| {
+            offset
+                .min(session_duration_mins - duration_mins.unwrap_or(0))
+                .max(0)
+        });
+
+    let notes: Vec<&str> = mappings
+        .iter()
+        .filter_map(|mapping| mapping.notes.as_deref())
+        .collect();
+
+    PiecePracticedMapping {
+        practice_session_id,
+        piece_id: target_piece_id,
+        duration_mins,
+        start_offset_mins,
+        notes: (!notes.is_empty()).then(|| notes.join("\n\n")),
+    }
+}
+
+// a user keeps one repertoire entry for the merged piece: the target's if they have one,
+// otherwise the most recently updated of the sources'
+fn merge_repertoire_entries(
+    conn: &mut PgConnection,
+    target_piece_id: i32,
+    source_piece_ids: &[i32],
+) -> QueryResult<usize> {
+    let users_with_target: Vec<i32> = repertoire::table
+        .select(repertoire::user_id)
+        .filter(repertoire::piece_id.eq(target_piece_id))
+        .load(conn)?;
+
+    diesel::delete(
+        repertoire::table
+            .filter(repertoire::piece_id.eq_any(source_piece_ids))
+            .filter(repertoire::user_id.eq_any(&users_with_target)),
+    )
+    .execute(conn)?;
+
+    let source_entries: Vec<(i32, i32)> = repertoire::table
+        .select((repertoire::user_id, repertoire::piece_id))
+        .filter(repertoire::piece_id.eq_any(source_piece_ids))
+        .order((repertoire::user_id, repertoire::updated_at.desc()))
+        .load(conn)?;
+
+    let mut kept_entries: HashMap<i32, i32> = HashMap::new();
+    for (user_id, piece_id) in source_entries {
+        kept_entries.entry(user_id).or_insert(piece_id);
+    }
+
+    let mut entries_repointed = 0;
+    for (user_id, piece_id) in kept_entries {
+        diesel::delete(
+            repertoire::table
+                .filter(repertoire::user_id.eq(user_id))
+                .filter(repertoire::piece_id.eq_any(source_piece_ids))
+                .filter(repertoire::piece_id.ne(piece_id)),
+        )
+        .execute(conn)?;
+
+        entries_repointed += diesel::update(repertoire::table.find((user_id, piece_id)))
+            .set(repertoire::piece_id.eq(target_piece_id))
+            .execute(conn)?;
+    }
+
+    Ok(entries_repointed)
+}
+
+// repoints every pieces practiced mapping, tempo entry, movement, repertoire entry and goal
+// of the source pieces to the target and deletes the sources; everything happens in one
+// transaction, so a failure leaves the catalogue untouched
+pub fn merge_pieces(
+    conn: &mut PgConnection,
+    target_piece_id: i32,
+    source_piece_ids: &[i32],
+) -> Result<MergeSummary, AppError> {
+    let mut source_piece_ids = source_piece_ids.to_vec();
+    source_piece_ids.sort();
+    source_piece_ids.dedup();
+
+    if source_piece_ids.is_empty() {
+        return Err(AppError::ClientError(
+            "No pieces given to merge into the target".to_owned(),
+        ));
+    }
+    if source_piece_ids.contains(&target_piece_id) {
+        return Err(AppError::ClientError(
+            "A piece cannot be merged into itself".to_owned(),
+        ));
+    }
+
+    let mut all_piece_ids = source_piece_ids.clone();
+    all_piece_ids.push(target_piece_id);
+
+    conn.transaction::<_, AppError, _>(|conn| {
+        let parent_piece_ids: HashMap<i32, Option<i32>> = pieces::table
+            .select((pieces::piece_id, pieces::parent_piece_id))
+            .filter(pieces::piece_id.eq_any(&all_piece_ids))
+            .for_update()
+            .load::<(i32, Option<i32>)>(conn)?
+            .into_iter()
+            .collect();
+
+        let missing_piece_ids: Vec<String> = all_piece_ids
+            .iter()
+            .filter(|piece_id| !parent_piece_ids.contains_key(piece_id))
+            .map(|piece_id| piece_id.to_string())
+            .collect();
+        if !missing_piece_ids.is_empty() {
+            return Err(AppError::NotFound(format!(
+                "Pieces not found: {}",
+                missing_piece_ids.join(", ")
+            )));
+        }
+
+        let target_parent_piece_id = parent_piece_ids[&target_piece_id];
+        if target_parent_piece_id
+            .is_some_and(|parent_piece_id| source_piece_ids.contains(&parent_piece_id))
+        {
+            return Err(AppError::ClientError(
+                "A piece cannot be merged into one of its own movements".to_owned(),
+            ));
+        }
+
+        // movements of the sources that aren't being merged themselves move to the target
+        let movements = pieces::table
+            .filter(pieces::parent_piece_id.eq_any(&source_piece_ids))
+            .filter(pieces::piece_id.ne_all(&all_piece_ids));
+
+        let num_movements: i64 = movements.clone().count().get_result(conn)?;
+        if num_movements > 0 && target_parent_piece_id.is_some() {
+            return Err(AppError::ClientError(
+                "Pieces with movements cannot be merged into a movement".to_owned(),
+            ));
+        }
+
+        let target_composer_id: i32 = pieces::table
+            .select(pieces::composer_id)
+            .filter(pieces::piece_id.eq(target_piece_id))
+            .first(conn)?;
+
+        let movements_repointed = diesel::update(movements)
+            .set((
+                pieces::parent_piece_id.eq(target_piece_id),
+                pieces::composer_id.eq(target_composer_id),
+            ))
+            .execute(conn)
+            .map_err(|e| match e {
+                DatabaseError(DatabaseErrorKind::UniqueViolation, _) => AppError::Conflict(
+                    "The target already has a movement with the same title as one being merged"
+                        .to_owned(),
+                ),
+                _ => AppError::BackendError(e.to_string()),
+            })?;
+
+        let mappings: Vec<PiecePracticedMapping> = pieces_practiced::table
+            .filter(pieces_practiced::piece_id.eq_any(&all_piece_ids))
+            .load::<PiecePracticedMapping>(conn)?;
+
+        // only sessions that include a source piece change
+        let mut mappings_by_session: BTreeMap<i32, Vec<PiecePracticedMapping>> = BTreeMap::new();
+        for mapping in mappings {
+            mappings_by_session
+                .entry(mapping.practice_session_id)
+                .or_default()
+                .push(mapping);
+        }
+        mappings_by_session.retain(|_, mappings| {
+            mappings
+                .iter()
+                .any(|mapping| mapping.piece_id != target_piece_id)
+        });
+
+        let session_durations: HashMap<i32, i32> = practice_sessions::table
+            .select((
+                practice_sessions::practice_session_id,
+                practice_sessions::duration_mins,
+            ))
+            .filter(
+                practice_sessions::practice_session_id
+                    .eq_any(mappings_by_session.keys().copied().collect::<Vec<i32>>()),
+            )
+            .load::<(i32, i32)>(conn)?
+            .into_iter()
+            .collect();
+
+        let mappings_combined = mappings_by_session
+            .values()
+            .filter(|mappings| mappings.len() > 1)
+            .count();
+        let mappings_repointed = mappings_by_session.len() - mappings_combined;
+
+        let merged_mappings: Vec<PiecePracticedMapping> = mappings_by_session
+            .iter()
+            .map(|(practice_session_id, mappings)| {
+                combine_mappings(
+                    *practice_session_id,
+                    target_piece_id,
+                    mappings,
+                    session_durations
+                        .get(practice_session_id)
+                        .copied()
+                        .unwrap_or(0),
+                )
+            })
+            .collect();
+
+        // tempo entries reference the mappings being replaced, the target's included, so
+        // they're set aside and added back to the merged mappings
+        let tempo_entries_to_repoint: Vec<TempoEntry> = tempo_entries::table
+            .filter(tempo_entries::piece_id.eq_any(&all_piece_ids))
+            .filter(
+                tempo_entries::practice_session_id
+                    .eq_any(mappings_by_session.keys().copied().collect::<Vec<i32>>()),
+            )
+            .load::<TempoEntry>(conn)?;
+        let tempo_entries_repointed = tempo_entries_to_repoint
+            .iter()
+            .filter(|tempo_entry| tempo_entry.piece_id != target_piece_id)
+            .count();
+
+        diesel::delete(
+            tempo_entries::table.filter(
+                tempo_entries::tempo_entry_id.eq_any(
+                    tempo_entries_to_repoint
+                        .iter()
+                        .map(|tempo_entry| tempo_entry.tempo_entry_id)
+                        .collect::<Vec<i32>>(),
+                ),
+            ),
+        )
+        .execute(conn)?;
+
+        diesel::delete(
+            pieces_practiced::table
+                .filter(pieces_practiced::piece_id.eq_any(&all_piece_ids))
+                .filter(
+                    pieces_practiced::practice_session_id
+                        .eq_any(mappings_by_session.keys().copied().collect::<Vec<i32>>()),
+                ),
+        )
+        .execute(conn)?;
+
+        diesel::insert_into(pieces_practiced::table)
+            .values(&merged_mappings)
+            .execute(conn)?;
+
+        diesel::insert_into(tempo_entries::table)
+            .values(
+                tempo_entries_to_repoint
+                    .into_iter()
+                    .map(|tempo_entry| {
+                        (
+                            tempo_entries::tempo_entry_id.eq(tempo_entry.tempo_entry_id),
+                            tempo_entries::practice_session_id.eq(tempo_entry.practice_session_id),
+                            tempo_entries::piece_id.eq(target_piece_id),
+                            tempo_entries::passage.eq(tempo_entry.passage),
+                            tempo_entries::target_bpm.eq(tempo_entry.target_bpm),
+                            tempo_entries::achieved_bpm.eq(tempo_entry.achieved_bpm),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .execute(conn)?;
+
+        let repertoire_entries_repointed =
+            merge_repertoire_entries(conn, target_piece_id, &source_piece_ids)?;
+
+        let goals_repointed = diesel::update(
+            practice_goals::table.filter(practice_goals::piece_id.eq_any(&source_piece_ids)),
+        )
+        .set(practice_goals::piece_id.eq(target_piece_id))
+        .execute(conn)?;
+
+        diesel::update(
+            practice_timer_segments::table
+                .filter(practice_timer_segments::piece_id.eq_any(&source_piece_ids)),
+        )
+        .set(practice_timer_segments::piece_id.eq(target_piece_id))
+        .execute(conn)?;
+
+        let pieces_merged =
+            diesel::delete(pieces::table.filter(pieces::piece_id.eq_any(&source_piece_ids)))
+                .execute(conn)?;
+
+        let target: Piece = map_backend_err!(pieces_with_composers()
+            .filter(pieces::piece_id.eq(target_piece_id))
+            .first::<Piece>(conn))?;
+
+        Ok(MergeSummary {
+            target,
+            pieces_merged,
+            mappings_repointed,
+            mappings_combined,
+            tempo_entries_repointed,
+            movements_repointed,
+            repertoire_entries_repointed,
+            goals_repointed,
+        })
+    })
+}
@@ -0,0 +1,162 @@
+use crate::calendar;
+use crate::models::PracticeSession;
+use crate::pagination::{Cursor, SortOrder};
+use crate::schema::practice_sessions;
+use crate::{
+    map_backend_err, order_after_cursor, with_pieces_practiced, AppError,
+    PracticeSessionWithPieces, PracticeSessionsQueryParams,
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use serde::Deserialize;
+use std::io::Write;
+
+const EXPORT_BATCH_SIZE: i64 = 500;
+
+const CSV_HEADER: [&str; 5] = [
+    "practice_session_id",
+    "start_datetime",
+    "duration_mins",
+    "instrument",
+    "pieces_practiced",
+];
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    Ics,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::JsonLines => "application/x-ndjson",
+            ExportFormat::Ics => "text/calendar; charset=utf-8",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Ics => "ics",
+        }
+    }
+}
+
+// pieces are written as "Composer: Title", separated by "; "
+fn format_pieces_practiced(practice_session: &PracticeSessionWithPieces) -> String {
+    practice_session
+        .pieces_practiced
+        .iter()
+        .map(|piece_practiced| {
+            format!(
+                "{}: {}",
+                piece_practiced.piece.composer, piece_practiced.piece.title
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+fn encode_batch(
+    format: ExportFormat,
+    practice_sessions: &[PracticeSessionWithPieces],
+    timezone: &Tz,
+) -> Result<Vec<u8>, AppError> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            for practice_session in practice_sessions {
+                // start times are written in the user's timezone, which is what spreadsheets expect
+                map_backend_err!(writer.write_record([
+                    practice_session.practice_session_id.to_string(),
+                    practice_session
+                        .start_datetime
+                        .with_timezone(timezone)
+                        .to_rfc3339(),
+                    practice_session.duration_mins.to_string(),
+                    practice_session.instrument.clone(),
+                    format_pieces_practiced(practice_session),
+                ]))?;
+            }
+            map_backend_err!(writer.into_inner())
+        }
+        ExportFormat::JsonLines => {
+            let mut buffer = Vec::new();
+            for practice_session in practice_sessions {
+                map_backend_err!(serde_json::to_writer(&mut buffer, practice_session))?;
+                buffer.push(b'\n');
+            }
+            Ok(buffer)
+        }
+        ExportFormat::Ics => {
+            Ok(calendar::encode_events(practice_sessions, Utc::now()).into_bytes())
+        }
+    }
+}
+
+// writes every practice session matching the filters, oldest first; sessions are loaded and
+// written in batches so the whole history never has to be held in memory
+pub fn write_practice_sessions(
+    conn: &mut PgConnection,
+    user_id: i32,
+    timezone: &Tz,
+    filters: &PracticeSessionsQueryParams,
+    format: ExportFormat,
+    out: &mut impl Write,
+) -> Result<(), AppError> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            map_backend_err!(writer.write_record(CSV_HEADER))?;
+            map_backend_err!(out.write_all(&map_backend_err!(writer.into_inner())?))?;
+        }
+        ExportFormat::Ics => {
+            map_backend_err!(out.write_all(calendar::calendar_header().as_bytes()))?;
+        }
+        ExportFormat::JsonLines => {}
+    }
+
+    let mut cursor: Option<Cursor<DateTime<Utc>>> = None;
+    loop {
+        let mut query = filters.filtered_query(user_id, timezone)?;
+        query = order_after_cursor!(
+            query,
+            practice_sessions::start_datetime,
+            practice_sessions::practice_session_id,
+            SortOrder::Asc,
+            cursor.take()
+        );
+
+        let batch: Vec<PracticeSession> =
+            map_backend_err!(query.limit(EXPORT_BATCH_SIZE).load::<PracticeSession>(conn))?;
+        let batch_len = batch.len() as i64;
+
+        cursor = batch.last().map(|last| Cursor {
+            value: last.start_datetime,
+            id: last.practice_session_id,
+        });
+
+        let batch = map_backend_err!(with_pieces_practiced(conn, batch))?;
+        map_backend_err!(out.write_all(&encode_batch(format, &batch, timezone)?))?;
+        map_backend_err!(out.flush())?;
+
+        if batch_len < EXPORT_BATCH_SIZE {
+            break;
+        }
+    }
+
+    if let ExportFormat::Ics = format {
+        map_backend_err!(out.write_all(calendar::calendar_footer().as_bytes()))?;
+        map_backend_err!(out.flush())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use crate::models::{GoalMetric, PracticeGoal};
+use crate::stats::Period;
+use crate::AppError;
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer, Nullable, Text, Timestamp};
+use serde::Serialize;
+use std::collections::HashMap;
+
+// progress towards a goal within one day/week/month in the user's timezone
+#[derive(Serialize)]
+pub struct GoalPeriodProgress {
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate, // the last day of the period
+    pub value: i64,
+    pub target: i32,
+    pub completed: bool,
+}
+
+#[derive(Serialize)]
+pub struct GoalWithProgress {
+    #[serde(flatten)]
+    pub goal: PracticeGoal,
+    pub progress: GoalPeriodProgress, // for the current period
+}
+
+#[derive(QueryableByName)]
+struct PeriodValue {
+    #[diesel(sql_type = Timestamp)]
+    period_start: NaiveDateTime,
+    #[diesel(sql_type = BigInt)]
+    value: i64,
+}
+
+// weeks start on monday, matching postgres' date_trunc
+pub fn period_start(date: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => date,
+        Period::Week => date - Duration::days(date.weekday().num_days_from_monday().into()),
+        Period::Month => date.with_day(1).unwrap_or(date),
+    }
+}
+
+fn next_period_start(start: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => start + Duration::days(1),
+        Period::Week => start + Duration::weeks(1),
+        Period::Month => start + Months::new(1),
+    }
+}
+
+fn previous_period_start(start: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => start - Duration::days(1),
+        Period::Week => start - Duration::weeks(1),
+        Period::Month => start - Months::new(1),
+    }
+}
+
+// the most a days goal can ask for, since it counts distinct days within the period
+pub fn max_days(period: Period) -> i32 {
+    match period {
+        Period::Day => 1,
+        Period::Week => 7,
+        Period::Month => 31,
+    }
+}
+
+// progress in each period from the one the goal was created in (or num_periods ago, whichever
+// is later) up to the one containing today, oldest first; periods without practice count as 0
+pub fn goal_progress(
+    conn: &mut PgConnection,
+    goal: &PracticeGoal,
+    timezone: &Tz,
+    today: NaiveDate,
+    num_periods: u32,
+) -> QueryResult<Vec<GoalPeriodProgress>> {
+    let current_start = period_start(today, goal.period);
+    let created_start = period_start(
+        goal.created_at.with_timezone(timezone).date_naive(),
+        goal.period,
+    );
+
+    let mut first_start = current_start;
+    for _ in 1..num_periods {
+        if first_start <= created_start {
+            break;
+        }
+        first_start = previous_period_start(first_start, goal.period);
+    }
+
+    let end = next_period_start(current_start, goal.period);
+
+    // like minutes_by_period, sessions fall in the period containing their local start time
+    let values: HashMap<NaiveDate, i64> = diesel::sql_query(
+        "SELECT date_trunc($1, ps.start_datetime AT TIME ZONE $2) AS period_start, \
+                (CASE $3 \
+                     WHEN 'minutes' THEN coalesce(sum( \
+                         CASE WHEN $5 IS NULL THEN ps.duration_mins ELSE pp.duration_mins END \
+                     ), 0) \
+                     WHEN 'sessions' THEN count(DISTINCT ps.practice_session_id) \
+                     ELSE count(DISTINCT (ps.start_datetime AT TIME ZONE $2)::DATE) \
+                 END)::BIGINT AS value \
+         FROM practice_sessions ps \
+         LEFT JOIN pieces_practiced pp \
+           ON pp.practice_session_id = ps.practice_session_id AND pp.piece_id = $5 \
+         WHERE ps.user_id = $4 \
+           AND ($5 IS NULL OR pp.piece_id IS NOT NULL) \
+           AND ($6 IS NULL OR ps.instrument_id = $6) \
+           AND ps.start_datetime AT TIME ZONE $2 >= $7 \
+           AND ps.start_datetime AT TIME ZONE $2 < $8 \
+         GROUP BY 1",
+    )
+    .bind::<Text, _>(goal.period.as_str())
+    .bind::<Text, _>(timezone.name())
+    .bind::<Text, _>(goal.metric.as_str())
+    .bind::<Integer, _>(goal.user_id)
+    .bind::<Nullable<Integer>, _>(goal.piece_id)
+    .bind::<Nullable<Integer>, _>(goal.instrument_id)
+    .bind::<Timestamp, _>(first_start.and_hms_opt(0, 0, 0).unwrap_or_default())
+    .bind::<Timestamp, _>(end.and_hms_opt(0, 0, 0).unwrap_or_default())
+    .load::<PeriodValue>(conn)?
+    .into_iter()
+    .map(|period_value| (period_value.period_start.date(), period_value.value))
+    .collect();
+
+    let mut progress = Vec::new();
+    let mut start = first_start;
+    while start < end {
+        let next_start = next_period_start(start, goal.period);
+        let value = values.get(&start).copied().unwrap_or(0);
+        progress.push(GoalPeriodProgress {
+            period_start: start,
+            period_end: next_start - Duration::days(1),
+            value,
+            target: goal.target,
+            completed: value >= goal.target.into(),
+        });
+        start = next_start;
+    }
+
+    Ok(progress)
+}
+
+pub fn with_current_progress(
+    conn: &mut PgConnection,
+    goal: PracticeGoal,
+    timezone: &Tz,
+    today: NaiveDate,
+) -> QueryResult<GoalWithProgress> {
+    let progress = goal_progress(conn, &goal, timezone, today, 1)?
+        .pop()
+        .expect("progress always includes the current period");
+
+    Ok(GoalWithProgress { goal, progress })
+}
+
+// targets have to be reachable within a single period
+pub fn validate_goal(metric: GoalMetric, period: Period, target: i32) -> Result<(), AppError> {
+    if target <= 0 {
+        return Err(AppError::ClientError(
+            "Goal targets must be positive".to_owned(),
+        ));
+    }
+
+    if metric == GoalMetric::Days && target > max_days(period) {
+        return Err(AppError::ClientError(format!(
+            "A {} has at most {} day(s) to practice on",
+            period.as_str(),
+            max_days(period)
+        )));
+    }
+
+    Ok(())
+}
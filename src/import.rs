@@ -0,0 +1,473 @@
+use crate::instruments::{get_or_create_instrument, normalize_instrument_name};
+use crate::models::{Piece, PracticeSession};
+use crate::schema::{composers, pieces, pieces_practiced, practice_sessions};
+use crate::{
+    escape_like, local_to_utc, map_backend_err, normalize_text, pieces_with_composers, AppError,
+    NewPiecePracticedData, NewPracticeSessionData, PiecesQuery,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// fuzzy matches scoring below this are reported as errors instead of guessed at
+const MIN_PIECE_SIMILARITY: f64 = 0.85;
+const MIN_FUZZY_WORD_LENGTH: usize = 3;
+const MAX_PIECE_CANDIDATES: i64 = 200;
+
+// column names match the csv export, so exported files can be imported again
+#[derive(Deserialize)]
+struct CsvRow {
+    #[serde(alias = "date")]
+    start_datetime: String,
+    #[serde(alias = "duration")]
+    duration_mins: String,
+    instrument: String,
+    #[serde(default, alias = "pieces")]
+    pieces_practiced: String,
+}
+
+// a row that passed validation, along with the pieces it was resolved to
+#[derive(Serialize)]
+pub struct ImportRow {
+    pub row: u64,
+    pub start_datetime: DateTime<Utc>,
+    pub duration_mins: u32,
+    pub instrument: String,
+    pub pieces_practiced: Vec<Piece>,
+}
+
+#[derive(Serialize)]
+pub struct ImportRowError {
+    pub row: u64,
+    pub error: String,
+}
+
+// accepts rfc 3339 timestamps, or dates and times without an offset in the user's timezone
+fn parse_start_datetime(start_datetime: &str, timezone: &Tz) -> Result<DateTime<Utc>, String> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(start_datetime) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+
+    let local_datetime = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(start_datetime, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(start_datetime, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .ok_or(format!("Invalid start datetime: {start_datetime}"))?;
+
+    local_to_utc(local_datetime, timezone)
+        .map_err(|_| format!("{start_datetime} does not exist in timezone {timezone}"))
+}
+
+// lowercases and strips accents, so "Dvorak" compares equal to "Dvořák"
+fn normalize(s: &str) -> String {
+    s.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn similarity(a: &str, b: &str) -> f64 {
+    strsim::jaro_winkler(&normalize(a), &normalize(b))
+}
+
+// composers are often given by surname alone, so also compare against each part of the name
+fn composer_similarity(given: &str, composer: &str) -> f64 {
+    composer
+        .split_whitespace()
+        .map(|name| similarity(given, name))
+        .fold(similarity(given, composer), f64::max)
+}
+
+// pg_trgm's similarity, used to rank candidates before the closer comparison below
+sql_function! {
+    #[sql_name = "similarity"]
+    fn trigram_similarity(
+        a: diesel::sql_types::Text,
+        b: diesel::sql_types::Text
+    ) -> diesel::sql_types::Float4;
+}
+
+// the candidates ranked by how close they are to the given title (and composer), best first
+fn rank_candidates(
+    candidates: Vec<Piece>,
+    composer: Option<&str>,
+    title: &str,
+) -> Vec<(f64, Piece)> {
+    let mut ranked: Vec<(f64, Piece)> = candidates
+        .into_iter()
+        .map(|candidate| {
+            let score = match composer {
+                Some(composer) => {
+                    (similarity(title, &candidate.title)
+                        + composer_similarity(composer, &candidate.composer))
+                        / 2.0
+                }
+                None => similarity(title, &candidate.title),
+            };
+            (score, candidate)
+        })
+        .collect();
+    ranked.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    ranked
+}
+
+enum PieceMatchError {
+    NotFound,
+    NoCloseMatch(Box<Piece>),          // the closest candidate
+    Ambiguous(Box<Piece>, Box<Piece>), // two equally close candidates
+}
+
+impl PieceMatchError {
+    fn message(&self, composer: Option<&str>, title: &str) -> String {
+        let piece_name = piece_name(composer, title);
+        match self {
+            PieceMatchError::NotFound => format!("Piece not found: \"{piece_name}\""),
+            PieceMatchError::NoCloseMatch(piece) => format!(
+                "No close match for piece \"{piece_name}\" (closest was \"{}: {}\")",
+                piece.composer, piece.title
+            ),
+            PieceMatchError::Ambiguous(piece, other_piece) => format!(
+                "Ambiguous piece \"{piece_name}\" (matches \"{}: {}\" and \"{}: {}\")",
+                piece.composer, piece.title, other_piece.composer, other_piece.title
+            ),
+        }
+    }
+}
+
+// the closest candidate if it's close enough; when two are equally close neither is guessed at
+fn closest_piece(
+    candidates: Vec<Piece>,
+    composer: Option<&str>,
+    title: &str,
+) -> Result<Piece, PieceMatchError> {
+    let mut ranked = rank_candidates(candidates, composer, title).into_iter();
+
+    match (ranked.next(), ranked.next()) {
+        (None, _) => Err(PieceMatchError::NotFound),
+        (Some((score, piece)), _) if score < MIN_PIECE_SIMILARITY => {
+            Err(PieceMatchError::NoCloseMatch(Box::new(piece)))
+        }
+        (Some((score, piece)), Some((next_score, next_piece))) if score == next_score => Err(
+            PieceMatchError::Ambiguous(Box::new(piece), Box::new(next_piece)),
+        ),
+        (Some((_, piece)), _) => Ok(piece),
+    }
+}
+
+// resolves "Composer: Title" (or just "Title") to a piece in the catalogue: pieces containing
+// every word given are preferred, otherwise pieces sharing any word are accepted if they are
+// close enough to allow for typos. Candidates are ranked in sql first, so the closest ones are
+// among those compared even in a large catalogue
+fn resolve_piece(conn: &mut PgConnection, piece: &str) -> Result<Result<Piece, String>, AppError> {
+    let (composer, title) = match piece.split_once(':') {
+        Some((composer, title)) => (Some(composer.trim()), title.trim()),
+        None => (None, piece.trim()),
+    };
+
+    let ranked = |query: PiecesQuery| {
+        query
+            .order((
+                trigram_similarity(
+                    normalize_text(pieces::title),
+                    normalize_text(title.to_owned()),
+                )
+                .desc(),
+                trigram_similarity(
+                    normalize_text(composers::name),
+                    normalize_text(composer.unwrap_or_default().to_owned()),
+                )
+                .desc(),
+                pieces::piece_id,
+            ))
+            .limit(MAX_PIECE_CANDIDATES)
+    };
+
+    let mut query = pieces_with_composers().into_boxed();
+    for word in title.split_whitespace() {
+        query = query.filter(pieces::title.ilike(format!("%{}%", escape_like(word))));
+    }
+    for word in composer.unwrap_or_default().split_whitespace() {
+        query = query.filter(composers::name.ilike(format!("%{}%", escape_like(word))));
+    }
+
+    let candidates: Vec<Piece> = map_backend_err!(ranked(query).load::<Piece>(conn))?;
+
+    let error = match closest_piece(candidates, composer, title) {
+        Ok(piece) => return Ok(Ok(piece)),
+        Err(e @ PieceMatchError::Ambiguous(..)) => return Ok(Err(e.message(composer, title))),
+        Err(e) => e,
+    };
+
+    let fuzzy_words: Vec<&str> = title
+        .split_whitespace()
+        .filter(|word| word.chars().count() >= MIN_FUZZY_WORD_LENGTH)
+        .collect();
+    // without any word long enough to match loosely on, the first pass has the final say
+    if fuzzy_words.is_empty() {
+        return Ok(Err(error.message(composer, title)));
+    }
+
+    let mut query = pieces_with_composers().into_boxed();
+    for word in fuzzy_words {
+        query = query.or_filter(pieces::title.ilike(format!("%{}%", escape_like(word))));
+    }
+
+    let candidates: Vec<Piece> = map_backend_err!(ranked(query).load::<Piece>(conn))?;
+
+    Ok(closest_piece(candidates, composer, title).map_err(|e| e.message(composer, title)))
+}
+
+fn piece_name(composer: Option<&str>, title: &str) -> String {
+    match composer {
+        Some(composer) => format!("{composer}: {title}"),
+        None => title.to_owned(),
+    }
+}
+
+fn parse_row(
+    conn: &mut PgConnection,
+    csv_row: CsvRow,
+    row: u64,
+    timezone: &Tz,
+) -> Result<Result<ImportRow, String>, AppError> {
+    let start_datetime = match parse_start_datetime(csv_row.start_datetime.trim(), timezone) {
+        Ok(start_datetime) => start_datetime,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let duration_mins = match csv_row.duration_mins.trim().parse::<u32>() {
+        Ok(duration_mins) => duration_mins,
+        Err(_) => return Ok(Err(format!("Invalid duration: {}", csv_row.duration_mins))),
+    };
+
+    resolve_row(
+        conn,
+        row,
+        start_datetime,
+        duration_mins,
+        &csv_row.instrument,
+        csv_row.pieces_practiced.split(';'),
+    )
+}
+
+// checks the instrument and resolves each "Composer: Title" to a piece, for rows whose start
+// datetime and duration have already been parsed
+pub(crate) fn resolve_row<'a>(
+    conn: &mut PgConnection,
+    row: u64,
+    start_datetime: DateTime<Utc>,
+    duration_mins: u32,
+    instrument: &str,
+    pieces: impl Iterator<Item = &'a str>,
+) -> Result<Result<ImportRow, String>, AppError> {
+    let instrument = match normalize_instrument_name(instrument) {
+        Ok(instrument) => instrument,
+        Err(AppError::ClientError(e)) => return Ok(Err(e)),
+        Err(e) => return Err(e),
+    };
+
+    let mut pieces_practiced: Vec<Piece> = Vec::new();
+    for piece in pieces.filter(|piece| !piece.trim().is_empty()) {
+        match resolve_piece(conn, piece)? {
+            Ok(piece)
+                if pieces_practiced
+                    .iter()
+                    .any(|p| p.piece_id == piece.piece_id) =>
+            {
+                return Ok(Err(format!(
+                    "Piece listed more than once: \"{}: {}\"",
+                    piece.composer, piece.title
+                )))
+            }
+            Ok(piece) => pieces_practiced.push(piece),
+            Err(e) => return Ok(Err(e)),
+        }
+    }
+
+    Ok(Ok(ImportRow {
+        row,
+        start_datetime,
+        duration_mins,
+        instrument,
+        pieces_practiced,
+    }))
+}
+
+// parses and validates every row, splitting them into rows that can be imported and errors
+pub fn parse_practice_sessions_csv(
+    conn: &mut PgConnection,
+    user_id: i32,
+    timezone: &Tz,
+    csv_data: &[u8],
+) -> Result<(Vec<ImportRow>, Vec<ImportRowError>), AppError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(csv_data);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| AppError::ClientError(format!("Invalid csv header: {e}")))?
+        .clone();
+
+    let mut parsed_rows: Vec<(u64, Result<ImportRow, String>)> = Vec::new();
+
+    for record in reader.records() {
+        let (row, parsed_row) = match record {
+            Ok(record) => {
+                let row = record
+                    .position()
+                    .map(|position| position.line())
+                    .unwrap_or(0);
+                match record.deserialize::<CsvRow>(Some(&headers)) {
+                    Ok(csv_row) => (row, parse_row(conn, csv_row, row, timezone)?),
+                    Err(e) => (row, Err(format!("Invalid row: {e}"))),
+                }
+            }
+            Err(e) => (
+                e.position().map(|position| position.line()).unwrap_or(0),
+                Err(format!("Invalid row: {e}")),
+            ),
+        };
+
+        parsed_rows.push((row, parsed_row));
+    }
+
+    check_conflicts(conn, user_id, parsed_rows)
+}
+
+// splits parsed rows into rows that can be imported and errors; rows clashing with an existing
+// practice session (or an earlier row) are errors too
+pub(crate) fn check_conflicts(
+    conn: &mut PgConnection,
+    user_id: i32,
+    parsed_rows: Vec<(u64, Result<ImportRow, String>)>,
+) -> Result<(Vec<ImportRow>, Vec<ImportRowError>), AppError> {
+    let mut import_rows: Vec<ImportRow> = Vec::new();
+    let mut errors: Vec<ImportRowError> = Vec::new();
+
+    for (row, parsed_row) in parsed_rows {
+        match parsed_row {
+            Ok(import_row)
+                if import_rows
+                    .iter()
+                    .any(|other| other.start_datetime == import_row.start_datetime) =>
+            {
+                errors.push(ImportRowError {
+                    row,
+                    error: "Another row has the same start datetime".to_owned(),
+                })
+            }
+            Ok(import_row) => import_rows.push(import_row),
+            Err(error) => errors.push(ImportRowError { row, error }),
+        }
+    }
+
+    let existing_start_datetimes: HashSet<DateTime<Utc>> =
+        map_backend_err!(practice_sessions::table
+            .select(practice_sessions::start_datetime)
+            .filter(practice_sessions::user_id.eq(user_id))
+            .filter(
+                practice_sessions::start_datetime.eq_any(
+                    import_rows
+                        .iter()
+                        .map(|import_row| import_row.start_datetime)
+                        .collect::<Vec<DateTime<Utc>>>()
+                )
+            )
+            .load::<DateTime<Utc>>(conn))?
+        .into_iter()
+        .collect();
+
+    let (import_rows, conflicting_rows): (Vec<ImportRow>, Vec<ImportRow>) = import_rows
+        .into_iter()
+        .partition(|import_row| !existing_start_datetimes.contains(&import_row.start_datetime));
+
+    errors.extend(
+        conflicting_rows
+            .into_iter()
+            .map(|import_row| ImportRowError {
+                row: import_row.row,
+                error: "A practice session at that time already exists".to_owned(),
+            }),
+    );
+    errors.sort_by_key(|error| error.row);
+
+    Ok((import_rows, errors))
+}
+
+// inserts all of the rows, or none of them if any fails
+pub fn insert_practice_sessions(
+    conn: &mut PgConnection,
+    user_id: i32,
+    import_rows: Vec<ImportRow>,
+) -> Result<Vec<PracticeSession>, AppError> {
+    conn.transaction::<_, AppError, _>(|conn| {
+        let mut inserted_practice_sessions = Vec::new();
+
+        for import_row in import_rows {
+            let instrument = get_or_create_instrument(conn, user_id, &import_row.instrument)?;
+
+            let practice_session_data = NewPracticeSessionData {
+                start_datetime: import_row.start_datetime,
+                duration_mins: import_row.duration_mins,
+                instrument: import_row.instrument,
+                pieces_practiced: import_row
+                    .pieces_practiced
+                    .iter()
+                    .map(|piece| NewPiecePracticedData {
+                        piece_id: piece.piece_id,
+                        duration_mins: None,
+                        start_offset_mins: None,
+                        notes: None,
+                        tempo_entries: None,
+                    })
+                    .collect(),
+                notes: None,
+                rating: None,
+                tags: None,
+            };
+
+            let inserted_practice_session: PracticeSession =
+                diesel::insert_into(practice_sessions::table)
+                    .values(
+                        practice_session_data.make_insertable(user_id, instrument.instrument_id)?,
+                    )
+                    .get_result(conn)
+                    .map_err(|e| match e {
+                        // another request created a session at this time since the rows were parsed
+                        DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                            AppError::Conflict(format!(
+                                "A practice session at the time in row {} already exists",
+                                import_row.row
+                            ))
+                        }
+                        _ => AppError::BackendError(e.to_string()),
+                    })?;
+
+            diesel::insert_into(pieces_practiced::table)
+                .values(
+                    practice_session_data
+                        .make_piece_mappings(inserted_practice_session.practice_session_id)?,
+                )
+                .execute(conn)?;
+
+            inserted_practice_sessions.push(inserted_practice_session);
+        }
+
+        Ok(inserted_practice_sessions)
+    })
+}
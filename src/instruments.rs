@@ -0,0 +1,215 @@
+use crate::models::{InsertableInstrument, Instrument, InstrumentFamily};
+use crate::schema::{instruments, practice_goals, practice_sessions, practice_timers};
+use crate::{non_empty, AppError};
+use diesel::prelude::*;
+use diesel::result::Error;
+use serde::{Deserialize, Serialize};
+
+const MAX_INSTRUMENT_NAME_LENGTH: usize = 20;
+
+// instrument names are unique per user ignoring case, matching the index on lower(name)
+sql_function! {
+    fn lower(text: diesel::sql_types::Text) -> diesel::sql_types::Text;
+}
+
+#[derive(Deserialize)]
+pub struct NewInstrumentData {
+    pub name: String,
+    pub family: Option<InstrumentFamily>,
+    pub notes: Option<String>,
+}
+
+impl NewInstrumentData {
+    pub fn make_insertable(&self, user_id: i32) -> Result<InsertableInstrument, AppError> {
+        Ok(InsertableInstrument {
+            user_id,
+            name: normalize_instrument_name(&self.name)?,
+            family: self.family,
+            notes: non_empty(&self.notes),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct InstrumentWithSessionCount {
+    #[serde(flatten)]
+    pub instrument: Instrument,
+    pub num_sessions: i64,
+}
+
+#[derive(Serialize)]
+pub struct MergeInstrumentsSummary {
+    pub instrument: Instrument, // the instrument everything was merged into
+    pub practice_sessions_repointed: usize,
+    pub goals_repointed: usize,
+    pub instruments_deleted: usize,
+}
+
+// names are trimmed and have runs of whitespace collapsed, but keep the user's capitalization
+pub fn normalize_instrument_name(name: &str) -> Result<String, AppError> {
+    let name = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    if name.is_empty() {
+        return Err(AppError::ClientError("Missing instrument".to_owned()));
+    }
+    if name.chars().count() > MAX_INSTRUMENT_NAME_LENGTH {
+        return Err(AppError::ClientError(format!(
+            "Instrument names can be at most {MAX_INSTRUMENT_NAME_LENGTH} characters"
+        )));
+    }
+
+    Ok(name)
+}
+
+pub fn load_instrument(
+    conn: &mut PgConnection,
+    user_id: i32,
+    instrument_id: i32,
+) -> Result<Instrument, AppError> {
+    instruments::table
+        .filter(instruments::instrument_id.eq(instrument_id))
+        .filter(instruments::user_id.eq(user_id))
+        .first::<Instrument>(conn)
+        .map_err(|e| match e {
+            Error::NotFound => AppError::NotFound("Instrument not found".to_owned()),
+            _ => AppError::BackendError(e.to_string()),
+        })
+}
+
+// the user's instrument with this name, ignoring case, creating it if they don't have one yet
+pub fn get_or_create_instrument(
+    conn: &mut PgConnection,
+    user_id: i32,
+    name: &str,
+) -> Result<Instrument, AppError> {
+    let name = normalize_instrument_name(name)?;
+
+    let find_instrument = |conn: &mut PgConnection| {
+        instruments::table
+            .filter(instruments::user_id.eq(user_id))
+            .filter(lower(instruments::name).eq(lower(&name)))
+            .first::<Instrument>(conn)
+            .optional()
+    };
+
+    if let Some(instrument) = find_instrument(conn)? {
+        return Ok(instrument);
+    }
+
+    // another request may have created it in the meantime
+    diesel::insert_into(instruments::table)
+        .values((
+            instruments::user_id.eq(user_id),
+            instruments::name.eq(&name),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    find_instrument(conn)?.ok_or(AppError::BackendError(format!(
+        "Instrument {name} could not be created"
+    )))
+}
+
+// all of the user's instruments, including ones without any sessions, alphabetically
+pub fn instruments_with_session_counts(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> QueryResult<Vec<InstrumentWithSessionCount>> {
+    Ok(instruments::table
+        .left_join(practice_sessions::table)
+        .filter(instruments::user_id.eq(user_id))
+        .group_by(instruments::instrument_id)
+        .select((
+            Instrument::as_select(),
+            diesel::dsl::count(practice_sessions::practice_session_id.nullable()),
+        ))
+        .order(lower(instruments::name))
+        .load::<(Instrument, i64)>(conn)?
+        .into_iter()
+        .map(|(instrument, num_sessions)| InstrumentWithSessionCount {
+            instrument,
+            num_sessions,
+        })
+        .collect())
+}
+
+// moves every practice session, goal and timer on the source instruments to the target and
+// deletes the sources, e.g. to fold "vln" into "Violin"; all of the instruments must be the
+// user's
+pub fn merge_instruments(
+    conn: &mut PgConnection,
+    user_id: i32,
+    target_instrument_id: i32,
+    source_instrument_ids: &[i32],
+) -> Result<MergeInstrumentsSummary, AppError> {
+    let mut source_instrument_ids = source_instrument_ids.to_vec();
+    source_instrument_ids.sort();
+    source_instrument_ids.dedup();
+
+    if source_instrument_ids.is_empty() {
+        return Err(AppError::ClientError(
+            "No instruments given to merge into the target".to_owned(),
+        ));
+    }
+    if source_instrument_ids.contains(&target_instrument_id) {
+        return Err(AppError::ClientError(
+            "An instrument cannot be merged into itself".to_owned(),
+        ));
+    }
+
+    conn.transaction::<_, AppError, _>(|conn| {
+        let instrument = load_instrument(conn, user_id, target_instrument_id)?;
+
+        let found_instrument_ids: Vec<i32> = instruments::table
+            .select(instruments::instrument_id)
+            .filter(instruments::user_id.eq(user_id))
+            .filter(instruments::instrument_id.eq_any(&source_instrument_ids))
+            .for_update()
+            .load(conn)?;
+
+        let missing_instrument_ids: Vec<String> = source_instrument_ids
+            .iter()
+            .filter(|instrument_id| !found_instrument_ids.contains(instrument_id))
+            .map(|instrument_id| instrument_id.to_string())
+            .collect();
+        if !missing_instrument_ids.is_empty() {
+            return Err(AppError::NotFound(format!(
+                "Instruments not found: {}",
+                missing_instrument_ids.join(", ")
+            )));
+        }
+
+        let practice_sessions_repointed = diesel::update(
+            practice_sessions::table
+                .filter(practice_sessions::instrument_id.eq_any(&source_instrument_ids)),
+        )
+        .set(practice_sessions::instrument_id.eq(target_instrument_id))
+        .execute(conn)?;
+
+        let goals_repointed = diesel::update(
+            practice_goals::table
+                .filter(practice_goals::instrument_id.eq_any(&source_instrument_ids)),
+        )
+        .set(practice_goals::instrument_id.eq(target_instrument_id))
+        .execute(conn)?;
+
+        diesel::update(
+            practice_timers::table
+                .filter(practice_timers::instrument_id.eq_any(&source_instrument_ids)),
+        )
+        .set(practice_timers::instrument_id.eq(target_instrument_id))
+        .execute(conn)?;
+
+        let instruments_deleted = diesel::delete(
+            instruments::table.filter(instruments::instrument_id.eq_any(&source_instrument_ids)),
+        )
+        .execute(conn)?;
+
+        Ok(MergeInstrumentsSummary {
+            instrument,
+            practice_sessions_repointed,
+            goals_repointed,
+            instruments_deleted,
+        })
+    })
+}
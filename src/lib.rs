@@ -1,19 +1,43 @@
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::r2d2::ConnectionManager;
 use diesel::result::Error;
 use diesel::{pg::PgConnection, r2d2::Pool};
 use log::error;
-use models::{InsertablePracticeSession, Piece, PracticeSession};
-use schema::practice_sessions;
+use models::{
+    InsertablePiece, InsertablePracticeSession, InsertableTempoEntry, Piece, PiecePracticedMapping,
+    PracticeSession, PracticeSessionTag, RepertoireStatus, Role, TempoEntry,
+};
+use schema::{
+    pieces, pieces_practiced, practice_goals, practice_session_tags, practice_sessions, repertoire,
+    tempo_entries, users,
+};
 use serde::{Deserialize, Serialize};
+use tags::TagMatch;
+pub mod calendar;
+pub mod composers;
+pub mod duplicates;
+pub mod export;
+pub mod goals;
+pub mod import;
+pub mod instruments;
 pub mod models;
+pub mod pagination;
 pub mod schema;
+pub mod search;
+pub mod session_store;
+pub mod stats;
+pub mod streaks;
+pub mod tags;
+pub mod timer;
 use dotenvy::dotenv;
 use serde_json::json;
+use std::collections::HashMap;
 use std::env;
 
 // returns the practice session id if the user does own it, otherwise errors
@@ -33,6 +57,185 @@ pub fn verify_practice_session_ownership(
         })
 }
 
+pub fn get_user_role(conn: &mut PgConnection, user_id: i32) -> Result<Role, AppError> {
+    map_backend_err!(users::table
+        .select(users::role)
+        .filter(users::user_id.eq(user_id))
+        .first::<Role>(conn))
+}
+
+// deletes the user along with all of their practice history; pieces they created are kept
+// for everyone else and just lose their creator
+pub fn delete_user(conn: &mut PgConnection, user_id: i32) -> Result<usize, AppError> {
+    conn.transaction::<_, AppError, _>(|conn| {
+        let practice_session_ids = practice_sessions::table
+            .select(practice_sessions::practice_session_id)
+            .filter(practice_sessions::user_id.eq(user_id));
+
+        diesel::delete(
+            tempo_entries::table
+                .filter(tempo_entries::practice_session_id.eq_any(practice_session_ids)),
+        )
+        .execute(conn)?;
+
+        diesel::delete(
+            pieces_practiced::table
+                .filter(pieces_practiced::practice_session_id.eq_any(practice_session_ids)),
+        )
+        .execute(conn)?;
+
+        diesel::delete(
+            practice_session_tags::table
+                .filter(practice_session_tags::practice_session_id.eq_any(practice_session_ids)),
+        )
+        .execute(conn)?;
+
+        diesel::delete(schema::tags::table.filter(schema::tags::user_id.eq(user_id)))
+            .execute(conn)?;
+
+        diesel::delete(practice_sessions::table.filter(practice_sessions::user_id.eq(user_id)))
+            .execute(conn)?;
+
+        diesel::delete(repertoire::table.filter(repertoire::user_id.eq(user_id))).execute(conn)?;
+
+        diesel::delete(practice_goals::table.filter(practice_goals::user_id.eq(user_id)))
+            .execute(conn)?;
+
+        timer::delete_timer(conn, user_id)?;
+
+        diesel::delete(schema::instruments::table.filter(schema::instruments::user_id.eq(user_id)))
+            .execute(conn)?;
+
+        session_store::delete_user_sessions(conn, user_id)?;
+
+        Ok(diesel::delete(users::table.filter(users::user_id.eq(user_id))).execute(conn)?)
+    })
+}
+
+// lowercases and strips accents and punctuation; defined in the piece similarity migration
+sql_function! {
+    fn normalize_text(text: diesel::sql_types::Text) -> diesel::sql_types::Text;
+}
+
+// pieces joined with their composers, which is what Piece is selected from
+pub fn pieces_with_composers() -> diesel::dsl::Select<
+    diesel::dsl::InnerJoin<pieces::table, schema::composers::table>,
+    diesel::dsl::AsSelect<Piece, Pg>,
+> {
+    pieces::table
+        .inner_join(schema::composers::table)
+        .select(Piece::as_select())
+}
+
+pub type PiecesQuery = diesel::dsl::IntoBoxed<
+    'static,
+    diesel::dsl::Select<
+        diesel::dsl::InnerJoin<pieces::table, schema::composers::table>,
+        diesel::dsl::AsSelect<Piece, Pg>,
+    >,
+    Pg,
+>;
+
+// returns the piece if the user may edit or delete it, which only its creator and admins can
+pub fn verify_piece_permission(
+    conn: &mut PgConnection,
+    piece_id: i32,
+    current_user_id: i32,
+) -> Result<Piece, AppError> {
+    let piece: Piece = pieces_with_composers()
+        .filter(pieces::piece_id.eq(piece_id))
+        .first::<Piece>(conn)
+        .map_err(|e| match e {
+            Error::NotFound => AppError::NotFound("Piece not found".to_owned()),
+            _ => AppError::BackendError(e.to_string()),
+        })?;
+
+    if piece.created_by != Some(current_user_id)
+        && get_user_role(conn, current_user_id)? < Role::Admin
+    {
+        return Err(AppError::Forbidden(
+            "Only the piece's creator or an admin can change it".to_owned(),
+        ));
+    }
+
+    Ok(piece)
+}
+
+// errors listing every mapping whose piece is unknown or appears more than once
+pub fn verify_pieces_practiced(
+    conn: &mut PgConnection,
+    mappings: &[PiecePracticedMapping],
+) -> Result<(), AppError> {
+    let piece_ids: Vec<i32> = mappings.iter().map(|mapping| mapping.piece_id).collect();
+
+    let existing_piece_ids: Vec<i32> = pieces::table
+        .select(pieces::piece_id)
+        .filter(pieces::piece_id.eq_any(&piece_ids))
+        .load::<i32>(conn)?;
+
+    let mut piece_errors: Vec<PiecePracticedError> = Vec::new();
+    for (index, &piece_id) in piece_ids.iter().enumerate() {
+        if !existing_piece_ids.contains(&piece_id) {
+            piece_errors.push(PiecePracticedError {
+                piece_id,
+                error: PiecePracticedErrorKind::Unknown,
+            });
+        } else if piece_ids[..index].contains(&piece_id) {
+            piece_errors.push(PiecePracticedError {
+                piece_id,
+                error: PiecePracticedErrorKind::Duplicated,
+            });
+        }
+    }
+
+    if piece_errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::PiecesPracticedError(piece_errors))
+    }
+}
+
+// "today" can be given by the client, so it's kept to a sane range that date arithmetic
+// around it can't take past chrono's limits
+pub fn verify_today(today: NaiveDate) -> Result<NaiveDate, AppError> {
+    if !(1900..=9999).contains(&today.year()) {
+        return Err(AppError::ClientError("Invalid value for today".to_owned()));
+    }
+
+    Ok(today)
+}
+
+// the user's preferred timezone, used to interpret local dates and times
+pub fn get_user_timezone(conn: &mut PgConnection, user_id: i32) -> Result<Tz, AppError> {
+    let timezone: String = users::table
+        .select(users::timezone)
+        .find(user_id)
+        .first(conn)?;
+
+    parse_timezone(&timezone)
+}
+
+pub fn parse_timezone(timezone: &str) -> Result<Tz, AppError> {
+    timezone
+        .parse::<Tz>()
+        .map_err(|_| AppError::ClientError(format!("Unknown timezone: {timezone}")))
+}
+
+// converts a wall clock time in the given timezone to utc, picking the earlier instant
+// when the time is ambiguous because of a daylight saving transition
+pub fn local_to_utc(
+    local_datetime: NaiveDateTime,
+    timezone: &Tz,
+) -> Result<DateTime<Utc>, AppError> {
+    timezone
+        .from_local_datetime(&local_datetime)
+        .earliest()
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .ok_or(AppError::ClientError(format!(
+            "{local_datetime} does not exist in timezone {timezone}"
+        )))
+}
+
 #[macro_export]
 macro_rules! map_backend_err {
     ($fallible:expr) => {
@@ -54,6 +257,19 @@ macro_rules! get_user_id {
     };
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiecePracticedErrorKind {
+    Unknown,
+    Duplicated,
+}
+
+#[derive(Serialize)]
+pub struct PiecePracticedError {
+    pub piece_id: i32,
+    pub error: PiecePracticedErrorKind,
+}
+
 pub enum AppError {
     BackendError(String),
     ClientError(String),
@@ -62,6 +278,14 @@ pub enum AppError {
     Forbidden(String),
     Conflict(String),
     NotFound(String),
+    PiecesPracticedError(Vec<PiecePracticedError>),
+}
+
+// lets `?` be used on diesel results inside transactions that return AppError
+impl From<Error> for AppError {
+    fn from(e: Error) -> Self {
+        AppError::BackendError(e.to_string())
+    }
 }
 
 impl IntoResponse for AppError {
@@ -98,30 +322,325 @@ impl IntoResponse for AppError {
                 StatusCode::NOT_FOUND,
                 Json(json!({"success": false, "error": info})),
             ),
+            AppError::PiecesPracticedError(piece_errors) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    json!({"success": false, "error": "Invalid pieces practiced", "pieces_practiced": piece_errors}),
+                ),
+            ),
         }
         .into_response()
     }
 }
 
+// errors if the time allocated to the pieces doesn't fit within the practice session
+pub fn verify_piece_allocations(
+    duration_mins: i32,
+    mappings: &[PiecePracticedMapping],
+) -> Result<(), AppError> {
+    // summed as i64 so large client-supplied allocations can't overflow
+    let allocated_mins: i64 = mappings
+        .iter()
+        .filter_map(|mapping| mapping.duration_mins)
+        .map(i64::from)
+        .sum();
+
+    if allocated_mins > duration_mins.into() {
+        return Err(AppError::ClientError(format!(
+            "Time allocated to pieces ({allocated_mins} mins) exceeds practice session duration ({duration_mins} mins)"
+        )));
+    }
+
+    for mapping in mappings {
+        if let Some(start_offset_mins) = mapping.start_offset_mins {
+            let end_mins =
+                i64::from(start_offset_mins) + i64::from(mapping.duration_mins.unwrap_or(0));
+            if end_mins > duration_mins.into() {
+                return Err(AppError::ClientError(format!(
+                    "Time allocated to piece {} extends past the end of the practice session",
+                    mapping.piece_id
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+const MAX_BPM: u32 = 1000;
+const MAX_PASSAGE_LENGTH: usize = 100;
+
+// pieces can be given as full Piece objects, since only the id and allocation are read
+#[derive(Deserialize)]
+pub struct NewPiecePracticedData {
+    pub piece_id: i32,
+    pub duration_mins: Option<u32>,
+    pub start_offset_mins: Option<u32>,
+    pub notes: Option<String>,
+    // left out to keep a piece's existing tempo entries when its session is updated
+    pub tempo_entries: Option<Vec<NewTempoEntryData>>,
+}
+
+impl NewPiecePracticedData {
+    pub fn make_mapping(
+        &self,
+        practice_session_id: i32,
+    ) -> Result<PiecePracticedMapping, AppError> {
+        let invalid_allocation =
+            |_| AppError::ClientError("Invalid piece practiced allocation".to_owned());
+
+        Ok(PiecePracticedMapping {
+            practice_session_id,
+            piece_id: self.piece_id,
+            duration_mins: self
+                .duration_mins
+                .map(i32::try_from)
+                .transpose()
+                .map_err(invalid_allocation)?,
+            start_offset_mins: self
+                .start_offset_mins
+                .map(i32::try_from)
+                .transpose()
+                .map_err(invalid_allocation)?,
+            notes: non_empty(&self.notes),
+        })
+    }
+
+    pub fn make_tempo_entries(
+        &self,
+        practice_session_id: i32,
+    ) -> Result<Vec<InsertableTempoEntry>, AppError> {
+        self.tempo_entries
+            .iter()
+            .flatten()
+            .map(|tempo_entry| tempo_entry.make_insertable(practice_session_id, self.piece_id))
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewTempoEntryData {
+    pub passage: Option<String>,
+    pub target_bpm: Option<u32>,
+    pub achieved_bpm: u32,
+}
+
+impl NewTempoEntryData {
+    pub fn make_insertable(
+        &self,
+        practice_session_id: i32,
+        piece_id: i32,
+    ) -> Result<InsertableTempoEntry, AppError> {
+        let bpm = |bpm: u32| match bpm {
+            1..=MAX_BPM => Ok(bpm as i32),
+            _ => Err(AppError::ClientError(format!(
+                "Tempos must be between 1 and {MAX_BPM} BPM"
+            ))),
+        };
+
+        // passages are compared ignoring case, but keep the spacing-normalized label as given
+        let passage = self
+            .passage
+            .as_deref()
+            .map(|passage| passage.split_whitespace().collect::<Vec<&str>>().join(" "))
+            .filter(|passage| !passage.is_empty());
+        if passage
+            .as_ref()
+            .is_some_and(|passage| passage.chars().count() > MAX_PASSAGE_LENGTH)
+        {
+            return Err(AppError::ClientError(format!(
+                "Passage labels can be at most {MAX_PASSAGE_LENGTH} characters"
+            )));
+        }
+
+        Ok(InsertableTempoEntry {
+            practice_session_id,
+            piece_id,
+            passage,
+            target_bpm: self.target_bpm.map(bpm).transpose()?,
+            achieved_bpm: bpm(self.achieved_bpm)?,
+        })
+    }
+}
+
+// the composer can be given by id, or by name to find or create it; movements can leave it
+// out to take the composer of their work
+#[derive(Deserialize)]
+pub struct NewPieceData {
+    pub title: String,
+    pub composer: Option<String>,
+    pub composer_id: Option<i32>,
+    pub catalogue_number: Option<String>,
+    pub key: Option<String>,
+    pub instrumentation: Option<String>,
+    pub genre: Option<String>,
+    pub parent_piece_id: Option<i32>,
+    pub movement_number: Option<u32>,
+}
+
+// escapes the wildcards in text given to LIKE/ILIKE, so it's only ever matched literally
+pub fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+// blank optional fields are stored as null, so they don't show up in searches
+pub(crate) fn non_empty(field: &Option<String>) -> Option<String> {
+    field
+        .as_deref()
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(str::to_owned)
+}
+
+impl NewPieceData {
+    pub fn make_insertable(
+        &self,
+        conn: &mut PgConnection,
+        created_by: Option<i32>,
+    ) -> Result<InsertablePiece, AppError> {
+        let parent: Option<Piece> = match self.parent_piece_id {
+            Some(parent_piece_id) => Some(
+                map_backend_err!(pieces_with_composers()
+                    .filter(pieces::piece_id.eq(parent_piece_id))
+                    .first::<Piece>(conn)
+                    .optional())?
+                .ok_or(AppError::NotFound("Parent piece not found".to_owned()))?,
+            ),
+            None => None,
+        };
+
+        if parent
+            .as_ref()
+            .is_some_and(|parent| parent.parent_piece_id.is_some())
+        {
+            return Err(AppError::ClientError(
+                "Movements cannot have movements of their own".to_owned(),
+            ));
+        }
+
+        let composer_id = match (self.composer_id, &self.composer, &parent) {
+            (Some(composer_id), _, _) => map_backend_err!(schema::composers::table
+                .select(schema::composers::composer_id)
+                .filter(schema::composers::composer_id.eq(composer_id))
+                .first::<i32>(conn)
+                .optional())?
+            .ok_or(AppError::NotFound("Composer not found".to_owned()))?,
+            (None, Some(composer), _) => {
+                composers::get_or_create_composer(conn, composer)?.composer_id
+            }
+            (None, None, Some(parent)) => parent.composer_id,
+            (None, None, None) => return Err(AppError::ClientError("Missing composer".to_owned())),
+        };
+
+        if parent
+            .as_ref()
+            .is_some_and(|parent| parent.composer_id != composer_id)
+        {
+            return Err(AppError::ClientError(
+                "A movement must have the same composer as its work".to_owned(),
+            ));
+        }
+
+        Ok(InsertablePiece {
+            title: self.title.trim().to_owned(),
+            composer_id,
+            created_by,
+            catalogue_number: non_empty(&self.catalogue_number),
+            key: non_empty(&self.key),
+            instrumentation: non_empty(&self.instrumentation),
+            genre: non_empty(&self.genre),
+            parent_piece_id: self.parent_piece_id,
+            movement_number: self
+                .movement_number
+                .map(i32::try_from)
+                .transpose()
+                .map_err(|_| AppError::ClientError("Invalid movement number".to_owned()))?,
+        })
+    }
+}
+
 #[derive(Deserialize)]
 pub struct NewPracticeSessionData {
-    pub start_datetime: chrono::NaiveDateTime,
+    pub start_datetime: DateTime<Utc>,
     pub duration_mins: u32,
-    pub instrument: String,
-    pub pieces_practiced: Vec<Piece>,
+    pub instrument: String, // instrument name, created for the user if they don't have it yet
+    pub pieces_practiced: Vec<NewPiecePracticedData>,
+    pub notes: Option<String>,
+    pub rating: Option<u32>,
+    // tag names, created for the user as needed; left out to keep the current tags on update
+    pub tags: Option<Vec<String>>,
 }
 
 impl NewPracticeSessionData {
-    pub fn make_insertable(&self, user_id: i32) -> Result<InsertablePracticeSession, AppError> {
+    pub fn make_insertable(
+        &self,
+        user_id: i32,
+        instrument_id: i32,
+    ) -> Result<InsertablePracticeSession, AppError> {
+        let rating = match self.rating {
+            Some(rating @ 1..=5) => Some(rating as i32),
+            Some(_) => {
+                return Err(AppError::ClientError(
+                    "Ratings must be between 1 and 5".to_owned(),
+                ))
+            }
+            None => None,
+        };
+
         Ok(InsertablePracticeSession {
             start_datetime: self.start_datetime,
             duration_mins: i32::try_from(self.duration_mins).map_err(|_| {
                 AppError::ClientError("Invalid practice session duration".to_owned())
             })?,
-            instrument: self.instrument.clone(),
             user_id,
+            notes: non_empty(&self.notes),
+            rating,
+            instrument_id,
         })
     }
+
+    pub fn make_piece_mappings(
+        &self,
+        practice_session_id: i32,
+    ) -> Result<Vec<PiecePracticedMapping>, AppError> {
+        let mappings = self
+            .pieces_practiced
+            .iter()
+            .map(|piece| piece.make_mapping(practice_session_id))
+            .collect::<Result<Vec<PiecePracticedMapping>, AppError>>()?;
+
+        let duration_mins = i32::try_from(self.duration_mins)
+            .map_err(|_| AppError::ClientError("Invalid practice session duration".to_owned()))?;
+        verify_piece_allocations(duration_mins, &mappings)?;
+
+        Ok(mappings)
+    }
+
+    // the tempo entries of every piece practiced, which are saved along with its mapping
+    pub fn make_tempo_entries(
+        &self,
+        practice_session_id: i32,
+    ) -> Result<Vec<InsertableTempoEntry>, AppError> {
+        Ok(self
+            .pieces_practiced
+            .iter()
+            .map(|piece| piece.make_tempo_entries(practice_session_id))
+            .collect::<Result<Vec<Vec<InsertableTempoEntry>>, AppError>>()?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    // pieces sent without tempo entries, whose existing ones are kept on update
+    pub fn pieces_keeping_tempo_entries(&self) -> Vec<i32> {
+        self.pieces_practiced
+            .iter()
+            .filter(|piece| piece.tempo_entries.is_none())
+            .map(|piece| piece.piece_id)
+            .collect()
+    }
 }
 
 #[derive(Deserialize)]
@@ -130,36 +649,329 @@ pub struct Credentials {
     pub password: String,
 }
 
+// a piece along with the time spent on it within a particular practice session, and where it
+// currently stands in the practicing user's repertoire
+#[derive(Serialize)]
+pub struct PiecePracticed {
+    #[serde(flatten)]
+    piece: Piece,
+    duration_mins: Option<i32>,
+    start_offset_mins: Option<i32>,
+    notes: Option<String>,
+    repertoire_status: Option<RepertoireStatus>,
+    tempo_entries: Vec<TempoEntry>,
+}
+
+impl PiecePracticed {
+    pub fn new(
+        mapping: PiecePracticedMapping,
+        piece: Piece,
+        repertoire_status: Option<RepertoireStatus>,
+        tempo_entries: Vec<TempoEntry>,
+    ) -> Self {
+        Self {
+            piece,
+            duration_mins: mapping.duration_mins,
+            start_offset_mins: mapping.start_offset_mins,
+            notes: mapping.notes,
+            repertoire_status,
+            tempo_entries,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct PracticeSessionWithPieces {
-    start_datetime: NaiveDateTime,
+    start_datetime: DateTime<Utc>,
     duration_mins: i32,
+    instrument_id: i32,
     instrument: String,
     practice_session_id: i32,
     user_id: i32,
-    pieces_practiced: Vec<Piece>,
+    notes: Option<String>,
+    rating: Option<i32>,
+    tags: Vec<String>,
+    pieces_practiced: Vec<PiecePracticed>,
 }
 
 impl PracticeSessionWithPieces {
-    pub fn new(db_practice_session: PracticeSession, pieces_practiced: Vec<Piece>) -> Self {
+    pub fn new(
+        db_practice_session: PracticeSession,
+        instrument: String,
+        tags: Vec<String>,
+        pieces_practiced: Vec<PiecePracticed>,
+    ) -> Self {
         let PracticeSession {
             start_datetime,
             duration_mins,
-            instrument,
             practice_session_id,
             user_id,
+            notes,
+            rating,
+            instrument_id,
         } = db_practice_session;
         Self {
             start_datetime,
             duration_mins,
+            instrument_id,
             instrument,
             practice_session_id,
             user_id,
+            notes,
+            rating,
+            tags,
             pieces_practiced,
         }
     }
 }
 
+// filters shared by everything that lists a user's practice sessions
+#[derive(Deserialize, Default)]
+pub struct PracticeSessionsQueryParams {
+    pub practice_session_id: Option<i32>,
+    pub min_datetime: Option<NaiveDateTime>,
+    pub max_datetime: Option<NaiveDateTime>,
+    pub min_duration_mins: Option<u32>,
+    pub max_duration_mins: Option<u32>,
+    pub instrument_id: Option<i32>,
+    pub instrument: Option<String>, // instrument name, ignoring case
+    // matches sessions whose notes, or notes on any piece in them, contain this text, ignoring
+    // case, accents and punctuation
+    pub notes: Option<String>,
+    pub tags: Option<String>,        // comma separated tag names
+    pub tag_match: Option<TagMatch>, // defaults to any
+}
+
+impl PracticeSessionsQueryParams {
+    // the practice sessions matching every filter, before sorting and pagination
+    pub fn filtered_query(
+        &self,
+        user_id: i32,
+        timezone: &Tz,
+    ) -> Result<practice_sessions::BoxedQuery<'static, Pg>, AppError> {
+        let mut query = practice_sessions::table
+            .into_boxed()
+            .filter(practice_sessions::user_id.eq(user_id));
+
+        if let Some(practice_session_id) = self.practice_session_id {
+            query = query.filter(practice_sessions::practice_session_id.eq(practice_session_id));
+        }
+
+        // datetime bounds are wall clock times in the user's timezone
+        if let Some(min_datetime) = self.min_datetime {
+            query = query.filter(
+                practice_sessions::start_datetime.ge(local_to_utc(min_datetime, timezone)?),
+            );
+        }
+
+        if let Some(max_datetime) = self.max_datetime {
+            query = query.filter(
+                practice_sessions::start_datetime.le(local_to_utc(max_datetime, timezone)?),
+            );
+        }
+
+        if let Some(min_duration_mins) = self.min_duration_mins {
+            query = query.filter(practice_sessions::duration_mins.ge(
+                i32::try_from(min_duration_mins).map_err(|_| {
+                    AppError::ClientError("Invalid value for min_duration_mins".to_owned())
+                })?,
+            ));
+        }
+
+        if let Some(max_duration_mins) = self.max_duration_mins {
+            query = query.filter(practice_sessions::duration_mins.le(
+                i32::try_from(max_duration_mins).map_err(|_| {
+                    AppError::ClientError("Invalid value for max_duration_mins".to_owned())
+                })?,
+            ));
+        }
+
+        if let Some(instrument_id) = self.instrument_id {
+            query = query.filter(practice_sessions::instrument_id.eq(instrument_id));
+        }
+
+        if let Some(instrument) = &self.instrument {
+            query = query.filter(
+                practice_sessions::instrument_id.eq_any(
+                    schema::instruments::table
+                        .select(schema::instruments::instrument_id)
+                        .filter(schema::instruments::user_id.eq(user_id))
+                        .filter(
+                            instruments::lower(schema::instruments::name)
+                                .eq(instruments::lower(instrument.trim().to_owned())),
+                        ),
+                ),
+            );
+        }
+
+        if let Some(tag_names) = &self.tags {
+            let tag_names = tag_names
+                .split(',')
+                .map(tags::normalize_tag_name)
+                .collect::<Result<Vec<String>, AppError>>()?;
+
+            let sessions_tagged_with = |tag_names: Vec<String>| {
+                practice_session_tags::table
+                    .inner_join(schema::tags::table)
+                    .select(practice_session_tags::practice_session_id)
+                    .filter(schema::tags::user_id.eq(user_id))
+                    .filter(schema::tags::name.eq_any(tag_names))
+            };
+
+            match self.tag_match.unwrap_or_default() {
+                TagMatch::Any => {
+                    query = query.filter(
+                        practice_sessions::practice_session_id
+                            .eq_any(sessions_tagged_with(tag_names)),
+                    );
+                }
+                TagMatch::All => {
+                    for tag_name in tag_names {
+                        query = query.filter(
+                            practice_sessions::practice_session_id
+                                .eq_any(sessions_tagged_with(vec![tag_name])),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(notes) = &self.notes {
+            // normalize_text strips % and _, so the search can't add wildcards of its own
+            let pattern = || {
+                "%".into_sql::<diesel::sql_types::Text>()
+                    .concat(normalize_text(notes.clone()))
+                    .concat("%")
+            };
+            // null notes never match, since normalize_text is strict
+            query = query.filter(
+                normalize_text(practice_sessions::notes.assume_not_null())
+                    .like(pattern())
+                    .or(practice_sessions::practice_session_id.eq_any(
+                        pieces_practiced::table
+                            .select(pieces_practiced::practice_session_id)
+                            .filter(
+                                normalize_text(pieces_practiced::notes.assume_not_null())
+                                    .like(pattern()),
+                            ),
+                    )),
+            );
+        }
+
+        Ok(query)
+    }
+}
+
+// attaches the pieces practiced in each practice session, keeping the sessions' order
+pub fn with_pieces_practiced(
+    conn: &mut PgConnection,
+    practice_sessions: Vec<PracticeSession>,
+) -> QueryResult<Vec<PracticeSessionWithPieces>> {
+    let pieces_practiced: Vec<Vec<(PiecePracticedMapping, Piece)>> =
+        PiecePracticedMapping::belonging_to(&practice_sessions)
+            .inner_join(pieces::table.inner_join(schema::composers::table))
+            .select((PiecePracticedMapping::as_select(), Piece::as_select()))
+            .load(conn)?
+            .grouped_by(&practice_sessions);
+
+    let session_tags: Vec<Vec<(PracticeSessionTag, String)>> =
+        PracticeSessionTag::belonging_to(&practice_sessions)
+            .inner_join(schema::tags::table)
+            .select((PracticeSessionTag::as_select(), schema::tags::name))
+            .order(schema::tags::name)
+            .load(conn)?
+            .grouped_by(&practice_sessions);
+
+    let tempo_entries: Vec<Vec<TempoEntry>> = TempoEntry::belonging_to(&practice_sessions)
+        .select(TempoEntry::as_select())
+        .order(tempo_entries::tempo_entry_id)
+        .load(conn)?
+        .grouped_by(&practice_sessions);
+
+    let instrument_names: HashMap<i32, String> = schema::instruments::table
+        .select((
+            schema::instruments::instrument_id,
+            schema::instruments::name,
+        ))
+        .filter(
+            schema::instruments::instrument_id.eq_any(
+                practice_sessions
+                    .iter()
+                    .map(|practice_session| practice_session.instrument_id),
+            ),
+        )
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .collect();
+
+    let repertoire_statuses: HashMap<(i32, i32), RepertoireStatus> = repertoire::table
+        .select((
+            repertoire::user_id,
+            repertoire::piece_id,
+            repertoire::status,
+        ))
+        .filter(
+            repertoire::user_id.eq_any(
+                practice_sessions
+                    .iter()
+                    .map(|practice_session| practice_session.user_id),
+            ),
+        )
+        .filter(
+            repertoire::piece_id.eq_any(
+                pieces_practiced
+                    .iter()
+                    .flatten()
+                    .map(|(mapping, _)| mapping.piece_id),
+            ),
+        )
+        .load::<(i32, i32, RepertoireStatus)>(conn)?
+        .into_iter()
+        .map(|(user_id, piece_id, status)| ((user_id, piece_id), status))
+        .collect();
+
+    // join together the practice sessions with the pieces practiced in each
+    Ok(practice_sessions
+        .into_iter()
+        .zip(session_tags)
+        .zip(pieces_practiced)
+        .zip(tempo_entries)
+        .map(
+            |(((practice_session, session_tags), pieces_practiced), tempo_entries)| {
+                let user_id = practice_session.user_id;
+                let mut tempo_entries_by_piece: HashMap<i32, Vec<TempoEntry>> = HashMap::new();
+                for tempo_entry in tempo_entries {
+                    tempo_entries_by_piece
+                        .entry(tempo_entry.piece_id)
+                        .or_default()
+                        .push(tempo_entry);
+                }
+                let instrument = instrument_names
+                    .get(&practice_session.instrument_id)
+                    .cloned()
+                    .unwrap_or_default();
+                PracticeSessionWithPieces::new(
+                    practice_session,
+                    instrument,
+                    session_tags.into_iter().map(|(_, name)| name).collect(),
+                    pieces_practiced
+                        .into_iter()
+                        .map(|(mapping, piece)| {
+                            let repertoire_status = repertoire_statuses
+                                .get(&(user_id, mapping.piece_id))
+                                .copied();
+                            let tempo_entries = tempo_entries_by_piece
+                                .remove(&mapping.piece_id)
+                                .unwrap_or_default();
+                            PiecePracticed::new(mapping, piece, repertoire_status, tempo_entries)
+                        })
+                        .collect(),
+                )
+            },
+        )
+        .collect())
+}
+
 pub fn establish_connection() -> Result<PgConnection, ConnectionError> {
     dotenv().expect(".env should load");
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL env var should be set");
@@ -1,335 +1,2380 @@
-use argon2;
-use axum::extract::{Path, Query, State};
-use axum::http::header::CONTENT_TYPE;
+use axum::body::{Bytes, StreamBody};
+use axum::extract::{FromRequestParts, Path, Query, State};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::request::Parts;
 use axum::http::{HeaderValue, Method, Request};
 use axum::middleware::{self, Next};
 use axum::response::{IntoResponse, Response};
-use axum::routing::{delete, get, post};
-use axum::{Json, Router};
+use axum::routing::{delete, get, post, put};
+use axum::{async_trait, Json, Router};
 use axum_sessions::{
-    async_session::MemoryStore,
     extractors::{ReadableSession, WritableSession},
     SessionLayer,
 };
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel::result::{DatabaseErrorKind, Error, Error::DatabaseError};
 use dotenvy::dotenv;
-use log::info;
-use practice_app::schema::{pieces, pieces_practiced, practice_sessions, users};
+use log::{error, info};
+use practice_app::calendar;
+use practice_app::composers::{make_sort_name, normalize_composer_name, ComposersQueryParams};
+use practice_app::duplicates;
+use practice_app::export::{self, ExportFormat};
+use practice_app::goals;
+use practice_app::import;
+use practice_app::instruments::{
+    get_or_create_instrument, instruments_with_session_counts, load_instrument, NewInstrumentData,
+};
+use practice_app::pagination::{offset, page_size, Cursor, SortOrder};
+use practice_app::schema::{
+    composers, instruments, pieces, pieces_practiced, practice_goals, practice_session_tags,
+    practice_sessions, practice_timer_segments, practice_timers, repertoire, tags, tempo_entries,
+    users,
+};
+use practice_app::session_store::{self, PgSessionStore};
+use practice_app::stats::{self, DateRange, Period};
+use practice_app::streaks::calculate_streaks;
+use practice_app::tags::{
+    load_practice_session_tags, normalize_tag_name, set_practice_session_tags,
+    tags_with_session_counts,
+};
+use practice_app::timer;
 use practice_app::{
-    get_connection_pool, get_db_conn, get_user_id, map_backend_err, models::*,
-    verify_practice_session_ownership, AppError, Credentials, NewPracticeSessionData,
-    PracticeSessionWithPieces,
+    delete_user, escape_like, get_connection_pool, get_db_conn, get_user_id, get_user_role,
+    get_user_timezone, local_to_utc, map_backend_err, models::*, order_after_cursor,
+    parse_timezone, pieces_with_composers, verify_piece_allocations, verify_piece_permission,
+    verify_pieces_practiced, verify_practice_session_ownership, verify_today,
+    with_pieces_practiced, AppError, Credentials, NewPieceData, NewPiecePracticedData,
+    NewPracticeSessionData, NewTempoEntryData, PiecesQuery, PracticeSessionWithPieces,
+    PracticeSessionsQueryParams,
 };
-use rand::{Rng, RngCore};
-use serde::Deserialize;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::env;
+use std::io;
+use std::marker::PhantomData;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tower_http::cors::CorsLayer;
 
+const SESSION_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+const SESSION_CLEANUP_PERIOD: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_STREAK_WINDOW_DAYS: u32 = 30;
+const MAX_STREAK_WINDOW_DAYS: u32 = 3660;
+const EXPORT_CHANNEL_CAPACITY: usize = 4;
+const DEFAULT_GOAL_HISTORY_PERIODS: u32 = 12;
+const MAX_GOAL_HISTORY_PERIODS: u32 = 366;
+
 struct AppState {
     db: Pool<ConnectionManager<PgConnection>>,
 }
 
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum PracticeSessionSortKey {
+    StartDatetime,
+    Duration,
+}
+
 #[derive(Deserialize)]
-struct PracticeSessionsQueryParams {
-    practice_session_id: Option<i32>,
-    min_datetime: Option<NaiveDateTime>,
-    max_datetime: Option<NaiveDateTime>,
-    min_duration_mins: Option<u32>,
-    max_duration_mins: Option<u32>,
-    instrument: Option<String>,
+struct PracticeSessionsPageParams {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    cursor: Option<String>, // next_cursor from the previous page, with the same sort and order
+    sort: Option<PracticeSessionSortKey>, // defaults to start_datetime
+    order: Option<SortOrder>, // defaults to desc
 }
 
 async fn get_practice_sessions(
     State(state): State<Arc<AppState>>,
     session: ReadableSession,
     query_params: Query<PracticeSessionsQueryParams>,
+    page_params: Query<PracticeSessionsPageParams>,
 ) -> Result<Json<Value>, AppError> {
     let current_user_id = get_user_id!(session)?;
 
+    let limit = page_size(page_params.limit)?;
+    let offset = offset(page_params.offset)?;
+    let sort_key = page_params
+        .sort
+        .unwrap_or(PracticeSessionSortKey::StartDatetime);
+    let sort_order = page_params.order.unwrap_or(SortOrder::Desc);
+
     let mut conn = get_db_conn!(state)?;
 
-    let mut query = practice_sessions::table
-        .into_boxed()
-        .filter(practice_sessions::user_id.eq(current_user_id));
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+
+    let total_count: i64 = map_backend_err!(query_params
+        .filtered_query(current_user_id, &timezone)?
+        .count()
+        .get_result(&mut conn))?;
+
+    let mut query = query_params.filtered_query(current_user_id, &timezone)?;
+
+    let cursor = page_params.cursor.as_deref();
+    query = match sort_key {
+        PracticeSessionSortKey::StartDatetime => order_after_cursor!(
+            query,
+            practice_sessions::start_datetime,
+            practice_sessions::practice_session_id,
+            sort_order,
+            cursor.map(Cursor::<DateTime<Utc>>::decode).transpose()?
+        ),
+        PracticeSessionSortKey::Duration => order_after_cursor!(
+            query,
+            practice_sessions::duration_mins,
+            practice_sessions::practice_session_id,
+            sort_order,
+            cursor.map(Cursor::<i32>::decode).transpose()?
+        ),
+    };
 
-    if let Some(practice_session_id) = query_params.practice_session_id {
-        query = query.filter(practice_sessions::practice_session_id.eq(practice_session_id));
-    }
+    let practice_sessions: Vec<PracticeSession> = map_backend_err!(query
+        .limit(limit)
+        .offset(offset)
+        .load::<PracticeSession>(&mut conn))?;
 
-    if let Some(min_datetime) = query_params.min_datetime {
-        query = query.filter(practice_sessions::start_datetime.ge(min_datetime));
-    }
+    // only hand out a cursor if there may be more rows after this page
+    let next_cursor: Option<String> = practice_sessions
+        .last()
+        .filter(|_| practice_sessions.len() as i64 == limit)
+        .map(|last| match sort_key {
+            PracticeSessionSortKey::StartDatetime => Cursor {
+                value: last.start_datetime,
+                id: last.practice_session_id,
+            }
+            .encode(),
+            PracticeSessionSortKey::Duration => Cursor {
+                value: last.duration_mins,
+                id: last.practice_session_id,
+            }
+            .encode(),
+        });
+
+    let practice_sessions: Vec<PracticeSessionWithPieces> =
+        map_backend_err!(with_pieces_practiced(&mut conn, practice_sessions))?;
 
-    if let Some(max_datetime) = query_params.max_datetime {
-        query = query.filter(practice_sessions::start_datetime.le(max_datetime));
+    Ok(Json(json!({
+        "success": true,
+        "practice_sessions": practice_sessions,
+        "total_count": total_count,
+        "next_cursor": next_cursor
+    })))
+}
+
+#[derive(Deserialize)]
+struct ExportQueryParams {
+    format: ExportFormat,
+}
+
+// forwards everything written to it as chunks of the response body
+struct ChannelWriter {
+    sender: mpsc::Sender<Result<Bytes, io::Error>>,
+}
+
+impl io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sender
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Client disconnected"))?;
+        Ok(buf.len())
     }
 
-    if let Some(min_duration_mins) = query_params.min_duration_mins {
-        query = query.filter(practice_sessions::duration_mins.ge(
-            i32::try_from(min_duration_mins).map_err(|_| {
-                AppError::ClientError("Invalid value for min_duration_mins".to_owned())
-            })?,
-        ));
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
+}
 
-    if let Some(max_duration_mins) = query_params.max_duration_mins {
-        query = query.filter(practice_sessions::duration_mins.le(
-            i32::try_from(max_duration_mins).map_err(|_| {
-                AppError::ClientError("Invalid value for max_duration_mins".to_owned())
-            })?,
-        ));
+async fn export_practice_sessions(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<PracticeSessionsQueryParams>,
+    export_params: Query<ExportQueryParams>,
+) -> Result<Response, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let format = export_params.format;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+
+    // surface invalid filters as a normal error response before the body starts streaming
+    let _query = query_params.filtered_query(current_user_id, &timezone)?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type().to_owned()),
+            (
+                CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"practice_sessions.{}\"",
+                    format.file_extension()
+                ),
+            ),
+        ],
+        stream_export(conn, current_user_id, timezone, query_params.0, format),
+    )
+        .into_response())
+}
+
+// runs the export on a blocking thread, streaming what it writes as the response body
+fn stream_export(
+    mut conn: PooledConnection<ConnectionManager<PgConnection>>,
+    user_id: i32,
+    timezone: Tz,
+    filters: PracticeSessionsQueryParams,
+    format: ExportFormat,
+) -> StreamBody<ReceiverStream<Result<Bytes, io::Error>>> {
+    let (sender, receiver) = mpsc::channel(EXPORT_CHANNEL_CAPACITY);
+
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ChannelWriter {
+            sender: sender.clone(),
+        };
+        let export_result = export::write_practice_sessions(
+            &mut conn,
+            user_id,
+            &timezone,
+            &filters,
+            format,
+            &mut writer,
+        );
+
+        if let Err(e) = export_result {
+            if let AppError::BackendError(info) = e {
+                error!("SERVER ERROR: {info}");
+            }
+            // abort the response so the client doesn't mistake a partial export for a full one
+            let _ = sender.blocking_send(Err(io::Error::other("Practice session export failed")));
+        }
+    });
+
+    StreamBody::new(ReceiverStream::new(receiver))
+}
+
+// the calendar feed is authenticated by the secret token in its url rather than a session,
+// since calendar apps subscribe to it without logging in
+async fn get_calendar(
+    State(state): State<Arc<AppState>>,
+    Path(calendar_token): Path<String>,
+) -> Result<Response, AppError> {
+    let calendar_token = calendar_token
+        .strip_suffix(".ics")
+        .unwrap_or(&calendar_token);
+
+    let mut conn = get_db_conn!(state)?;
+
+    let user_id: i32 = map_backend_err!(users::table
+        .select(users::user_id)
+        .filter(users::calendar_token.eq(calendar_token))
+        .first::<i32>(&mut conn)
+        .optional())?
+    .ok_or(AppError::NotFound("Calendar not found".to_owned()))?;
+
+    let timezone = get_user_timezone(&mut conn, user_id)?;
+
+    Ok((
+        [(CONTENT_TYPE, ExportFormat::Ics.content_type().to_owned())],
+        stream_export(
+            conn,
+            user_id,
+            timezone,
+            PracticeSessionsQueryParams::default(),
+            ExportFormat::Ics,
+        ),
+    )
+        .into_response())
+}
+
+// replaces any existing token, so a leaked feed url can be revoked by creating a new one
+async fn create_calendar_token(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let calendar_token = calendar::generate_calendar_token();
+
+    let mut conn = get_db_conn!(state)?;
+
+    let rows_updated: usize = map_backend_err!(diesel::update(
+        users::table.filter(users::user_id.eq(current_user_id))
+    )
+    .set(users::calendar_token.eq(&calendar_token))
+    .execute(&mut conn))?;
+
+    Ok(Json(json!({
+        "success": rows_updated > 0,
+        "calendar_token": calendar_token,
+        "calendar_url": format!("/api/calendar/{calendar_token}.ics")
+    })))
+}
+
+async fn delete_calendar_token(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let rows_updated: usize = map_backend_err!(diesel::update(
+        users::table.filter(users::user_id.eq(current_user_id))
+    )
+    .set(users::calendar_token.eq(None::<String>))
+    .execute(&mut conn))?;
+
+    Ok(Json(json!({ "success": rows_updated > 0 })))
+}
+
+#[derive(Deserialize)]
+struct ImportQueryParams {
+    dry_run: Option<bool>, // validate and resolve pieces without inserting anything
+}
+
+async fn import_practice_sessions(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<ImportQueryParams>,
+    csv_data: String,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let dry_run = query_params.dry_run.unwrap_or(false);
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+
+    let (import_rows, errors) = import::parse_practice_sessions_csv(
+        &mut conn,
+        current_user_id,
+        &timezone,
+        csv_data.as_bytes(),
+    )?;
+
+    finish_import(&mut conn, current_user_id, dry_run, import_rows, errors)
+}
+
+#[derive(Deserialize)]
+struct ImportCalendarQueryParams {
+    dry_run: Option<bool>,
+    instrument: Option<String>, // used for every event instead of the instrument in its summary
+}
+
+async fn import_calendar(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<ImportCalendarQueryParams>,
+    ics_data: String,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let dry_run = query_params.dry_run.unwrap_or(false);
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+
+    let (import_rows, errors) = calendar::parse_practice_sessions_ics(
+        &mut conn,
+        current_user_id,
+        &timezone,
+        &ics_data,
+        query_params.instrument.as_deref(),
+    )?;
+
+    finish_import(&mut conn, current_user_id, dry_run, import_rows, errors)
+}
+
+// inserts the rows that passed validation, or just reports them on a dry run
+fn finish_import(
+    conn: &mut PgConnection,
+    user_id: i32,
+    dry_run: bool,
+    import_rows: Vec<import::ImportRow>,
+    errors: Vec<import::ImportRowError>,
+) -> Result<Json<Value>, AppError> {
+    if dry_run {
+        return Ok(Json(json!({
+            "success": true,
+            "dry_run": true,
+            "practice_sessions": import_rows,
+            "errors": errors
+        })));
     }
 
-    if let Some(instrument) = &query_params.instrument {
-        query = query.filter(practice_sessions::instrument.eq(instrument));
+    let inserted_practice_sessions = import::insert_practice_sessions(conn, user_id, import_rows)?;
+    let practice_sessions: Vec<PracticeSessionWithPieces> =
+        map_backend_err!(with_pieces_practiced(conn, inserted_practice_sessions))?;
+
+    Ok(Json(json!({
+        "success": true,
+        "dry_run": false,
+        "practice_sessions": practice_sessions,
+        "errors": errors
+    })))
+}
+
+// a newly inserted practice session along with everything saved with it
+struct InsertedPracticeSession {
+    practice_session: PracticeSession,
+    instrument: Instrument,
+    pieces_practiced: Vec<PiecePracticedMapping>,
+    tempo_entries: Vec<TempoEntry>,
+    tags: Vec<Tag>,
+}
+
+// inserts the practice session along with its pieces practiced, tempo entries and tags; shared
+// by creating a session directly and stopping a practice timer
+fn insert_practice_session(
+    conn: &mut PgConnection,
+    user_id: i32,
+    practice_session_data: &NewPracticeSessionData,
+) -> Result<InsertedPracticeSession, AppError> {
+    let instrument = get_or_create_instrument(conn, user_id, &practice_session_data.instrument)?;
+
+    let new_practice_session =
+        practice_session_data.make_insertable(user_id, instrument.instrument_id)?;
+
+    let inserted_practice_session: PracticeSession = diesel::insert_into(practice_sessions::table)
+        .values(new_practice_session)
+        .get_result(conn)
+        .map_err(|e| match e {
+            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                AppError::Conflict("A practice session at that time already exists".to_string())
+            }
+            _ => AppError::BackendError(e.to_string()),
+        })?;
+
+    let pieces_practiced_mappings: Vec<PiecePracticedMapping> =
+        practice_session_data.make_piece_mappings(inserted_practice_session.practice_session_id)?;
+
+    verify_pieces_practiced(conn, &pieces_practiced_mappings)?;
+
+    let pieces_practiced_inserted = diesel::insert_into(pieces_practiced::table)
+        .values(pieces_practiced_mappings)
+        .get_results::<PiecePracticedMapping>(conn)?;
+
+    let tempo_entries_inserted = diesel::insert_into(tempo_entries::table)
+        .values(
+            practice_session_data
+                .make_tempo_entries(inserted_practice_session.practice_session_id)?,
+        )
+        .get_results::<TempoEntry>(conn)?;
+
+    let tags = set_practice_session_tags(
+        conn,
+        user_id,
+        inserted_practice_session.practice_session_id,
+        practice_session_data.tags.as_deref().unwrap_or_default(),
+    )?;
+
+    Ok(InsertedPracticeSession {
+        practice_session: inserted_practice_session,
+        instrument,
+        pieces_practiced: pieces_practiced_inserted,
+        tempo_entries: tempo_entries_inserted,
+        tags,
+    })
+}
+
+async fn create_practice_session(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(practice_session_data): Json<NewPracticeSessionData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    // the practice session is only kept if all of its pieces practiced mappings are valid
+    let inserted = conn.transaction::<_, AppError, _>(|conn| {
+        insert_practice_session(conn, current_user_id, &practice_session_data)
+    })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "practice_session": inserted.practice_session,
+        "instrument": inserted.instrument,
+        "pieces_practiced": inserted.pieces_practiced,
+        "tempo_entries": inserted.tempo_entries,
+        "tags": inserted.tags
+    })))
+}
+
+async fn update_practice_session(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(practice_session_id): Path<i32>,
+    Json(practice_session_data): Json<NewPracticeSessionData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let pieces_practiced_mappings: Vec<PiecePracticedMapping> =
+        practice_session_data.make_piece_mappings(practice_session_id)?;
+    let new_tempo_entries = practice_session_data.make_tempo_entries(practice_session_id)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    // the session row and its piece list are replaced together, so a failure part way
+    // through leaves the practice session exactly as it was
+    let (
+        updated_practice_session,
+        instrument,
+        pieces_practiced_updated,
+        tempo_entries_updated,
+        tags,
+    ) = conn.transaction::<_, AppError, _>(|conn| {
+        let _practice_session_id =
+            verify_practice_session_ownership(conn, practice_session_id, current_user_id)?;
+
+        let instrument =
+            get_or_create_instrument(conn, current_user_id, &practice_session_data.instrument)?;
+
+        let updated_practice_session =
+            practice_session_data.make_insertable(current_user_id, instrument.instrument_id)?;
+
+        let updated_practice_session: PracticeSession = diesel::update(
+            practice_sessions::table
+                .filter(practice_sessions::user_id.eq(current_user_id))
+                .filter(practice_sessions::practice_session_id.eq(practice_session_id)),
+        )
+        .set(&updated_practice_session)
+        .get_result(conn)
+        .map_err(|e| match e {
+            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                AppError::Conflict("A practice session at that time already exists".to_string())
+            }
+            _ => AppError::BackendError(e.to_string()),
+        })?;
+
+        // tempo entries reference the mappings being replaced, so those of pieces that stay in
+        // the session without new tempo entries are set aside and added back afterwards
+        let kept_tempo_entries: Vec<TempoEntry> = tempo_entries::table
+            .filter(tempo_entries::practice_session_id.eq(practice_session_id))
+            .filter(
+                tempo_entries::piece_id
+                    .eq_any(practice_session_data.pieces_keeping_tempo_entries()),
+            )
+            .load::<TempoEntry>(conn)?;
+
+        diesel::delete(
+            tempo_entries::table.filter(tempo_entries::practice_session_id.eq(practice_session_id)),
+        )
+        .execute(conn)?;
+
+        diesel::delete(
+            pieces_practiced::table
+                .filter(pieces_practiced::practice_session_id.eq(practice_session_id)),
+        )
+        .execute(conn)?;
+
+        verify_pieces_practiced(conn, &pieces_practiced_mappings)?;
+
+        let pieces_practiced_updated = diesel::insert_into(pieces_practiced::table)
+            .values(pieces_practiced_mappings)
+            .get_results::<PiecePracticedMapping>(conn)?;
+
+        diesel::insert_into(tempo_entries::table)
+            .values(
+                kept_tempo_entries
+                    .into_iter()
+                    .map(|tempo_entry| {
+                        (
+                            tempo_entries::tempo_entry_id.eq(tempo_entry.tempo_entry_id),
+                            tempo_entries::practice_session_id.eq(tempo_entry.practice_session_id),
+                            tempo_entries::piece_id.eq(tempo_entry.piece_id),
+                            tempo_entries::passage.eq(tempo_entry.passage),
+                            tempo_entries::target_bpm.eq(tempo_entry.target_bpm),
+                            tempo_entries::achieved_bpm.eq(tempo_entry.achieved_bpm),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .execute(conn)?;
+
+        diesel::insert_into(tempo_entries::table)
+            .values(new_tempo_entries)
+            .execute(conn)?;
+
+        let tempo_entries_updated: Vec<TempoEntry> = tempo_entries::table
+            .filter(tempo_entries::practice_session_id.eq(practice_session_id))
+            .order(tempo_entries::tempo_entry_id)
+            .load(conn)?;
+
+        let tags = match &practice_session_data.tags {
+            Some(tag_names) => {
+                set_practice_session_tags(conn, current_user_id, practice_session_id, tag_names)?
+            }
+            None => load_practice_session_tags(conn, practice_session_id)?,
+        };
+
+        Ok((
+            updated_practice_session,
+            instrument,
+            pieces_practiced_updated,
+            tempo_entries_updated,
+            tags,
+        ))
+    })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "practice_session": updated_practice_session,
+        "instrument": instrument,
+        "pieces_practiced": pieces_practiced_updated,
+        "tempo_entries": tempo_entries_updated,
+        "tags": tags
+    })))
+}
+
+async fn delete_practice_session(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(practice_session_id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let (rows_deleted, pieces_practiced_deleted) = conn.transaction::<_, AppError, _>(|conn| {
+        let _practice_session_id =
+            verify_practice_session_ownership(conn, practice_session_id, current_user_id)?;
+
+        // delete the pieces practiced mappings (and their tempo entries) that link to this
+        // practice session first...
+        diesel::delete(
+            tempo_entries::table.filter(tempo_entries::practice_session_id.eq(practice_session_id)),
+        )
+        .execute(conn)?;
+
+        let pieces_practiced_deleted: usize = diesel::delete(
+            pieces_practiced::table
+                .filter(pieces_practiced::practice_session_id.eq(practice_session_id)),
+        )
+        .execute(conn)?;
+
+        diesel::delete(
+            practice_session_tags::table
+                .filter(practice_session_tags::practice_session_id.eq(practice_session_id)),
+        )
+        .execute(conn)?;
+
+        // ...then delete practice session itself
+        let rows_deleted: usize = diesel::delete(
+            practice_sessions::table
+                .filter(practice_sessions::user_id.eq(current_user_id))
+                .filter(practice_sessions::practice_session_id.eq(practice_session_id)),
+        )
+        .execute(conn)?;
+
+        Ok((rows_deleted, pieces_practiced_deleted))
+    })?;
+
+    Ok(Json(
+        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted, "pieces_practiced_mappings_deleted": pieces_practiced_deleted }),
+    ))
+}
+
+#[derive(Deserialize)]
+struct StatsQueryParams {
+    min_datetime: Option<NaiveDateTime>,
+    max_datetime: Option<NaiveDateTime>,
+}
+
+impl StatsQueryParams {
+    // the bounds are given as wall clock times in the user's timezone
+    fn date_range(&self, timezone: &Tz) -> Result<DateRange, AppError> {
+        Ok(DateRange {
+            min_datetime: self
+                .min_datetime
+                .map(|min_datetime| local_to_utc(min_datetime, timezone))
+                .transpose()?,
+            max_datetime: self
+                .max_datetime
+                .map(|max_datetime| local_to_utc(max_datetime, timezone))
+                .transpose()?,
+        })
     }
+}
 
-    let practice_sessions: Vec<PracticeSession> = map_backend_err!(query
-        .order(practice_sessions::start_datetime.desc())
-        .load::<PracticeSession>(&mut conn))?;
+#[derive(Deserialize)]
+struct PeriodStatsQueryParams {
+    period: Period,
+    #[serde(flatten)]
+    range: StatsQueryParams,
+}
+
+async fn get_minutes_by_period(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<PeriodStatsQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let date_range = query_params.range.date_range(&timezone)?;
+
+    let totals = map_backend_err!(stats::minutes_by_period(
+        &mut conn,
+        current_user_id,
+        query_params.period,
+        &timezone,
+        &date_range
+    ))?;
+
+    Ok(Json(json!({ "success": true, "stats": totals })))
+}
+
+async fn get_minutes_by_instrument(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<StatsQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let date_range = query_params.date_range(&timezone)?;
+
+    let totals = map_backend_err!(stats::minutes_by_instrument(
+        &mut conn,
+        current_user_id,
+        &date_range
+    ))?;
+
+    Ok(Json(json!({ "success": true, "stats": totals })))
+}
+
+async fn get_minutes_by_tag(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<StatsQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let date_range = query_params.date_range(&timezone)?;
+
+    let totals = map_backend_err!(stats::minutes_by_tag(
+        &mut conn,
+        current_user_id,
+        &date_range
+    ))?;
+
+    Ok(Json(json!({ "success": true, "stats": totals })))
+}
+
+// not flattened with the date range, since flattening loses the piece id's type
+#[derive(Deserialize)]
+struct TempoSeriesQueryParams {
+    piece_id: i32,
+    passage: Option<String>, // only this passage, ignoring case
+}
+
+async fn get_tempo_series(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<TempoSeriesQueryParams>,
+    range_params: Query<StatsQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let date_range = range_params.date_range(&timezone)?;
+
+    let series = map_backend_err!(stats::tempo_series(
+        &mut conn,
+        current_user_id,
+        query_params.piece_id,
+        query_params.passage.as_deref(),
+        &date_range
+    ))?;
+
+    Ok(Json(json!({ "success": true, "series": series })))
+}
+
+async fn get_minutes_by_piece(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<StatsQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let date_range = query_params.date_range(&timezone)?;
+
+    let totals = map_backend_err!(stats::minutes_by_piece(
+        &mut conn,
+        current_user_id,
+        &date_range
+    ))?;
+
+    Ok(Json(json!({ "success": true, "stats": totals })))
+}
+
+async fn get_minutes_by_composer(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<StatsQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let date_range = query_params.date_range(&timezone)?;
+
+    let totals = map_backend_err!(stats::minutes_by_composer(
+        &mut conn,
+        current_user_id,
+        &date_range
+    ))?;
+
+    Ok(Json(json!({ "success": true, "stats": totals })))
+}
+
+#[derive(Deserialize)]
+struct StreaksQueryParams {
+    days: Option<u32>, // size of the window for days practiced and weekday averages
+    today: Option<NaiveDate>, // defaults to the current date in the user's timezone
+}
+
+async fn get_streaks(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<StreaksQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let window_days = query_params.days.unwrap_or(DEFAULT_STREAK_WINDOW_DAYS);
+    if window_days > MAX_STREAK_WINDOW_DAYS {
+        return Err(AppError::ClientError("Invalid value for days".to_owned()));
+    }
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+
+    // streaks are counted in days as the user experiences them, so use local start times
+    let practice_sessions: Vec<(NaiveDateTime, i32)> = map_backend_err!(practice_sessions::table
+        .select((
+            practice_sessions::start_datetime,
+            practice_sessions::duration_mins
+        ))
+        .filter(practice_sessions::user_id.eq(current_user_id))
+        .load::<(DateTime<Utc>, i32)>(&mut conn))?
+    .into_iter()
+    .map(|(start_datetime, duration_mins)| {
+        (
+            start_datetime.with_timezone(&timezone).naive_local(),
+            duration_mins,
+        )
+    })
+    .collect();
+
+    let today = match query_params.today {
+        Some(today) => verify_today(today)?,
+        None => Utc::now().with_timezone(&timezone).date_naive(),
+    };
+
+    let streaks = calculate_streaks(&practice_sessions, today, window_days);
+
+    Ok(Json(json!({ "success": true, "streaks": streaks })))
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum PieceSortKey {
+    Title,
+    Composer,
+}
+
+#[derive(Deserialize)]
+struct GetPiecesQueryParams {
+    piece_id: Option<i32>,
+    title: Option<String>,    // match containing
+    composer: Option<String>, // match containing
+    composer_id: Option<i32>,
+    catalogue_number: Option<String>, // exact match, ignoring case
+    key: Option<String>,              // exact match, ignoring case
+    genre: Option<String>,            // exact match, ignoring case
+    parent_piece_id: Option<i32>,     // the movements of a work
+    // every word must appear in the title, composer, catalogue number, key, instrumentation
+    // or genre, so "bach bwv 1007" and "sonata d minor" both work
+    search: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    cursor: Option<String>, // next_cursor from the previous page, with the same sort and order
+    sort: Option<PieceSortKey>, // defaults to title
+    order: Option<SortOrder>, // defaults to asc
+}
+
+impl GetPiecesQueryParams {
+    // the pieces matching every filter, before sorting and pagination
+    fn filtered_query(&self) -> PiecesQuery {
+        let mut query = pieces_with_composers().into_boxed();
+
+        if let Some(piece_id) = self.piece_id {
+            query = query.filter(pieces::piece_id.eq(piece_id));
+        }
+
+        if let Some(title) = &self.title {
+            query = query.filter(pieces::title.ilike(format!("%{}%", title)));
+        }
+
+        if let Some(composer) = &self.composer {
+            query = query.filter(composers::name.ilike(format!("%{}%", composer)));
+        }
+
+        if let Some(composer_id) = self.composer_id {
+            query = query.filter(pieces::composer_id.eq(composer_id));
+        }
+
+        if let Some(catalogue_number) = &self.catalogue_number {
+            query = query.filter(pieces::catalogue_number.ilike(escape_like(catalogue_number)));
+        }
+
+        if let Some(key) = &self.key {
+            query = query.filter(pieces::key.ilike(escape_like(key)));
+        }
+
+        if let Some(genre) = &self.genre {
+            query = query.filter(pieces::genre.ilike(escape_like(genre)));
+        }
+
+        if let Some(parent_piece_id) = self.parent_piece_id {
+            query = query.filter(pieces::parent_piece_id.eq(parent_piece_id));
+        }
+
+        if let Some(search) = &self.search {
+            for word in search.split_whitespace() {
+                let pattern = format!("%{}%", escape_like(word));
+                query = query.filter(
+                    pieces::title
+                        .ilike(pattern.clone())
+                        .or(composers::name.ilike(pattern.clone()))
+                        .nullable()
+                        .or(pieces::catalogue_number.ilike(pattern.clone()))
+                        .or(pieces::key.ilike(pattern.clone()))
+                        .or(pieces::instrumentation.ilike(pattern.clone()))
+                        .or(pieces::genre.ilike(pattern))
+                        .assume_not_null(),
+                );
+            }
+        }
+
+        query
+    }
+}
+
+async fn get_pieces(
+    State(state): State<Arc<AppState>>,
+    query_params: Query<GetPiecesQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let limit = page_size(query_params.limit)?;
+    let offset = offset(query_params.offset)?;
+    let sort_key = query_params.sort.unwrap_or(PieceSortKey::Title);
+    let sort_order = query_params.order.unwrap_or(SortOrder::Asc);
+
+    let mut conn = get_db_conn!(state)?;
+
+    let total_count: i64 =
+        map_backend_err!(query_params.filtered_query().count().get_result(&mut conn))?;
+
+    let mut query = query_params.filtered_query();
+
+    let cursor = query_params
+        .cursor
+        .as_deref()
+        .map(Cursor::<String>::decode)
+        .transpose()?;
+    query = match sort_key {
+        PieceSortKey::Title => {
+            order_after_cursor!(query, pieces::title, pieces::piece_id, sort_order, cursor)
+        }
+        PieceSortKey::Composer => {
+            order_after_cursor!(query, composers::name, pieces::piece_id, sort_order, cursor)
+        }
+    };
+
+    let pieces: Vec<Piece> =
+        map_backend_err!(query.limit(limit).offset(offset).load::<Piece>(&mut conn))?;
+
+    // only hand out a cursor if there may be more rows after this page
+    let next_cursor: Option<String> =
+        pieces
+            .last()
+            .filter(|_| pieces.len() as i64 == limit)
+            .map(|last| {
+                Cursor {
+                    value: match sort_key {
+                        PieceSortKey::Title => last.title.clone(),
+                        PieceSortKey::Composer => last.composer.clone(),
+                    },
+                    id: last.piece_id,
+                }
+                .encode()
+            });
+
+    Ok(Json(json!({
+        "success": true,
+        "pieces": pieces,
+        "total_count": total_count,
+        "next_cursor": next_cursor
+    })))
+}
+
+#[derive(Deserialize)]
+struct ComposersPageParams {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    cursor: Option<String>, // next_cursor from the previous page
+}
+
+// composers are listed by sort name, so "Bach, Johann Sebastian" comes before "Mozart, ..."
+async fn get_composers(
+    State(state): State<Arc<AppState>>,
+    query_params: Query<ComposersQueryParams>,
+    page_params: Query<ComposersPageParams>,
+) -> Result<Json<Value>, AppError> {
+    let limit = page_size(page_params.limit)?;
+    let offset = offset(page_params.offset)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let total_count: i64 =
+        map_backend_err!(query_params.filtered_query().count().get_result(&mut conn))?;
+
+    let mut query = query_params.filtered_query();
+    query = order_after_cursor!(
+        query,
+        composers::sort_name,
+        composers::composer_id,
+        SortOrder::Asc,
+        page_params
+            .cursor
+            .as_deref()
+            .map(Cursor::<String>::decode)
+            .transpose()?
+    );
+
+    let composers: Vec<Composer> = map_backend_err!(query
+        .limit(limit)
+        .offset(offset)
+        .load::<Composer>(&mut conn))?;
+
+    let next_cursor: Option<String> = composers
+        .last()
+        .filter(|_| composers.len() as i64 == limit)
+        .map(|last| {
+            Cursor {
+                value: last.sort_name.clone(),
+                id: last.composer_id,
+            }
+            .encode()
+        });
+
+    Ok(Json(json!({
+        "success": true,
+        "composers": composers,
+        "total_count": total_count,
+        "next_cursor": next_cursor
+    })))
+}
+
+#[derive(Deserialize)]
+struct SearchPiecesQueryParams {
+    query: String,
+    limit: Option<i64>,
+}
+
+async fn search_pieces(
+    State(state): State<Arc<AppState>>,
+    query_params: Query<SearchPiecesQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let limit = page_size(query_params.limit)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let pieces = practice_app::search::search_pieces(&mut conn, &query_params.query, limit)?;
+
+    Ok(Json(json!({ "success": true, "pieces": pieces })))
+}
+
+#[derive(Deserialize)]
+struct SearchComposersQueryParams {
+    query: String,
+    limit: Option<i64>,
+}
+
+async fn search_composers(
+    State(state): State<Arc<AppState>>,
+    query_params: Query<SearchComposersQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let limit = page_size(query_params.limit)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let composers =
+        practice_app::composers::search_composers(&mut conn, &query_params.query, limit)?;
+
+    Ok(Json(json!({ "success": true, "composers": composers })))
+}
+
+async fn admin_update_composer(
+    State(state): State<Arc<AppState>>,
+    _admin: Authorized<Admin>,
+    Path(composer_id): Path<i32>,
+    Json(composer_data): Json<InsertableComposer>,
+) -> Result<Json<Value>, AppError> {
+    let name = normalize_composer_name(&composer_data.name)?.to_owned();
+    // a blank sort name is worked out from the name, as it is for new composers
+    let sort_name = match composer_data.sort_name.trim() {
+        "" => make_sort_name(&name),
+        sort_name => sort_name.to_owned(),
+    };
+    let composer_data = InsertableComposer {
+        name,
+        sort_name,
+        ..composer_data
+    };
+
+    let mut conn = get_db_conn!(state)?;
+
+    let updated_composer: Composer =
+        diesel::update(composers::table.filter(composers::composer_id.eq(composer_id)))
+            .set(&composer_data)
+            .get_result(&mut conn)
+            .map_err(|e| match e {
+                Error::NotFound => AppError::NotFound("Composer not found".to_owned()),
+                DatabaseError(DatabaseErrorKind::UniqueViolation, _) => AppError::Conflict(
+                    "Another composer already has that name or Open Opus id".to_string(),
+                ),
+                _ => AppError::BackendError(e.to_string()),
+            })?;
+
+    Ok(Json(
+        json!({ "success": true, "composer": updated_composer }),
+    ))
+}
+
+async fn create_piece(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(piece_data): Json<NewPieceData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    // a composer created for the piece is only kept if the piece is
+    let inserted_piece: Piece = conn.transaction::<_, AppError, _>(|conn| {
+        let new_piece = piece_data.make_insertable(conn, Some(current_user_id))?;
+
+        let piece_id: i32 = diesel::insert_into(pieces::table)
+            .values(new_piece)
+            .returning(pieces::piece_id)
+            .get_result(conn)
+            .map_err(|e| match e {
+                DatabaseError(DatabaseErrorKind::UniqueViolation, _) => AppError::Conflict(
+                    "That piece is already registered in the database".to_string(),
+                ),
+                _ => AppError::BackendError(e.to_string()),
+            })?;
+
+        Ok(pieces_with_composers()
+            .filter(pieces::piece_id.eq(piece_id))
+            .first::<Piece>(conn)?)
+    })?;
+
+    Ok(Json(json!({ "success": true, "piece": inserted_piece })))
+}
+
+async fn update_piece(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(piece_id): Path<i32>,
+    Json(piece_data): Json<NewPieceData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let updated_piece: Piece = conn.transaction::<_, AppError, _>(|conn| {
+        let piece = verify_piece_permission(conn, piece_id, current_user_id)?;
+
+        if piece_data.parent_piece_id == Some(piece_id) {
+            return Err(AppError::ClientError(
+                "A piece cannot be a movement of itself".to_owned(),
+            ));
+        }
+
+        let updated_piece = piece_data.make_insertable(conn, piece.created_by)?;
+
+        if updated_piece.parent_piece_id.is_some() {
+            let num_movements: i64 = pieces::table
+                .filter(pieces::parent_piece_id.eq(piece_id))
+                .count()
+                .get_result(conn)?;
+            if num_movements > 0 {
+                return Err(AppError::ClientError(
+                    "A piece with movements cannot become a movement".to_owned(),
+                ));
+            }
+        }
+
+        diesel::update(pieces::table.filter(pieces::piece_id.eq(piece_id)))
+            .set(&updated_piece)
+            .execute(conn)
+            .map_err(|e| match e {
+                DatabaseError(DatabaseErrorKind::UniqueViolation, _) => AppError::Conflict(
+                    "That piece is already registered in the database".to_string(),
+                ),
+                _ => AppError::BackendError(e.to_string()),
+            })?;
+
+        // movements always share the composer of their work
+        diesel::update(pieces::table.filter(pieces::parent_piece_id.eq(piece_id)))
+            .set(pieces::composer_id.eq(updated_piece.composer_id))
+            .execute(conn)?;
+
+        Ok(pieces_with_composers()
+            .filter(pieces::piece_id.eq(piece_id))
+            .first::<Piece>(conn)?)
+    })?;
+
+    Ok(Json(json!({ "success": true, "piece": updated_piece })))
+}
+
+#[derive(Deserialize)]
+struct DeletePieceQueryParams {
+    // admins only: also remove the piece from every practice session it appears in
+    remove_from_history: Option<bool>,
+}
+
+// pieces that have been practiced are kept, since deleting them would silently rewrite
+// practice history (possibly other users'), unless an admin explicitly asks for that
+async fn delete_piece(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(piece_id): Path<i32>,
+    query_params: Query<DeletePieceQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let remove_from_history = query_params.remove_from_history.unwrap_or(false);
+
+    let mut conn = get_db_conn!(state)?;
+
+    let (rows_deleted, mappings_deleted) = conn.transaction::<_, AppError, _>(|conn| {
+        let _piece = verify_piece_permission(conn, piece_id, current_user_id)?;
+
+        let num_movements: i64 = pieces::table
+            .filter(pieces::parent_piece_id.eq(piece_id))
+            .count()
+            .get_result(conn)?;
+        if num_movements > 0 {
+            return Err(AppError::Conflict(format!(
+                "That piece has {num_movements} movement(s), which must be deleted first"
+            )));
+        }
+
+        let times_practiced: i64 = pieces_practiced::table
+            .filter(pieces_practiced::piece_id.eq(piece_id))
+            .count()
+            .get_result(conn)?;
+
+        let mappings_deleted = match times_practiced {
+            0 => 0,
+            _ if remove_from_history && get_user_role(conn, current_user_id)? >= Role::Admin => {
+                diesel::delete(tempo_entries::table.filter(tempo_entries::piece_id.eq(piece_id)))
+                    .execute(conn)?;
+                diesel::delete(
+                    pieces_practiced::table.filter(pieces_practiced::piece_id.eq(piece_id)),
+                )
+                .execute(conn)?
+            }
+            _ if remove_from_history => {
+                return Err(AppError::Forbidden(
+                    "Only admins can remove a piece from practice history".to_owned(),
+                ))
+            }
+            _ => {
+                return Err(AppError::Conflict(format!(
+                    "That piece appears in {times_practiced} practice session(s)"
+                )))
+            }
+        };
+
+        // repertoire entries and goals are only plans, so unlike practice history they go with
+        // the piece
+        diesel::delete(repertoire::table.filter(repertoire::piece_id.eq(piece_id)))
+            .execute(conn)?;
+        diesel::delete(practice_goals::table.filter(practice_goals::piece_id.eq(piece_id)))
+            .execute(conn)?;
+        // a running timer keeps its time, just without the piece
+        diesel::update(
+            practice_timer_segments::table.filter(practice_timer_segments::piece_id.eq(piece_id)),
+        )
+        .set(practice_timer_segments::piece_id.eq(None::<i32>))
+        .execute(conn)?;
+
+        let rows_deleted =
+            diesel::delete(pieces::table.filter(pieces::piece_id.eq(piece_id))).execute(conn)?;
+
+        Ok((rows_deleted, mappings_deleted))
+    })?;
+
+    Ok(Json(json!({
+        "success": rows_deleted > 0,
+        "num_deleted": rows_deleted,
+        "pieces_practiced_deleted": mappings_deleted
+    })))
+}
+
+#[derive(Deserialize)]
+struct PiecePracticedData {
+    practice_session_id: i32,
+    #[serde(flatten)]
+    piece_practiced: NewPiecePracticedData,
+}
+
+async fn create_piece_practiced(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(piece_practiced_data): Json<PiecePracticedData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let practice_session_id = piece_practiced_data.practice_session_id;
+    let piece_practiced_mapping = piece_practiced_data
+        .piece_practiced
+        .make_mapping(practice_session_id)?;
+    let new_tempo_entries = piece_practiced_data
+        .piece_practiced
+        .make_tempo_entries(practice_session_id)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let (inserted_mapping, tempo_entries_inserted) =
+        conn.transaction::<_, AppError, _>(|conn| {
+            // verify that the practice session in the mapping belongs to the current user
+            let _practice_session_id =
+                verify_practice_session_ownership(conn, practice_session_id, current_user_id)?;
+
+            // the new piece's allocation has to fit alongside the pieces already in the session
+            let duration_mins: i32 = practice_sessions::table
+                .select(practice_sessions::duration_mins)
+                .find(practice_session_id)
+                .first(conn)?;
+
+            let mut session_mappings: Vec<PiecePracticedMapping> = pieces_practiced::table
+                .filter(pieces_practiced::practice_session_id.eq(practice_session_id))
+                .load::<PiecePracticedMapping>(conn)?;
+            session_mappings.push(piece_practiced_mapping.clone());
+
+            verify_piece_allocations(duration_mins, &session_mappings)?;
+
+            let inserted_mapping = diesel::insert_into(pieces_practiced::table)
+                .values(piece_practiced_mapping)
+                .get_result::<PiecePracticedMapping>(conn)
+                .map_err(|e| match e {
+                    DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                        AppError::Conflict("Entry already exists".to_string())
+                    }
+                    DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+                        // "piece not found" must be the case because:
+                        // 1: practice session existence is verified above using verify_practice_session_ownership!()
+                        // 2: piece_id is the only foreign key remaining
+                        AppError::ClientError("Piece not found".to_string())
+                    }
+                    _ => AppError::BackendError(e.to_string()),
+                })?;
+
+            let tempo_entries_inserted = diesel::insert_into(tempo_entries::table)
+                .values(new_tempo_entries)
+                .get_results::<TempoEntry>(conn)?;
+
+            Ok((inserted_mapping, tempo_entries_inserted))
+        })?;
+
+    Ok(Json(json!({
+        "success": true,
+        "piece_practiced": inserted_mapping,
+        "tempo_entries": tempo_entries_inserted
+    })))
+}
+
+async fn delete_piece_practiced(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path((practice_session_id, piece_id)): Path<(i32, i32)>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let rows_deleted = conn.transaction::<_, AppError, _>(|conn| {
+        // verify that the practice session in the mapping belongs to the current user
+        let _practice_session_id =
+            verify_practice_session_ownership(conn, practice_session_id, current_user_id)?;
+
+        diesel::delete(
+            tempo_entries::table
+                .filter(tempo_entries::piece_id.eq(piece_id))
+                .filter(tempo_entries::practice_session_id.eq(practice_session_id)),
+        )
+        .execute(conn)?;
+
+        Ok(diesel::delete(
+            pieces_practiced::table
+                .filter(pieces_practiced::piece_id.eq(piece_id))
+                .filter(pieces_practiced::practice_session_id.eq(practice_session_id)),
+        )
+        .execute(conn)?)
+    })?;
+
+    Ok(Json(
+        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted }),
+    ))
+}
+
+// tempo entries can also be logged one at a time, e.g. while still practicing
+#[derive(Deserialize)]
+struct TempoEntryData {
+    practice_session_id: i32,
+    piece_id: i32,
+    #[serde(flatten)]
+    tempo_entry: NewTempoEntryData,
+}
+
+async fn create_tempo_entry(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(tempo_entry_data): Json<TempoEntryData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let new_tempo_entry = tempo_entry_data.tempo_entry.make_insertable(
+        tempo_entry_data.practice_session_id,
+        tempo_entry_data.piece_id,
+    )?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let _practice_session_id = verify_practice_session_ownership(
+        &mut conn,
+        tempo_entry_data.practice_session_id,
+        current_user_id,
+    )?;
+
+    let inserted_tempo_entry: TempoEntry = diesel::insert_into(tempo_entries::table)
+        .values(new_tempo_entry)
+        .get_result(&mut conn)
+        .map_err(|e| match e {
+            // the practice session is verified above, so the piece isn't part of it
+            DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => AppError::NotFound(
+                "That piece was not practiced in the practice session".to_owned(),
+            ),
+            _ => AppError::BackendError(e.to_string()),
+        })?;
+
+    Ok(Json(
+        json!({ "success": true, "tempo_entry": inserted_tempo_entry }),
+    ))
+}
+
+async fn delete_tempo_entry(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(tempo_entry_id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    // only the user's own tempo entries can be deleted
+    let tempo_entry_id: Option<i32> = map_backend_err!(tempo_entries::table
+        .inner_join(practice_sessions::table)
+        .select(tempo_entries::tempo_entry_id)
+        .filter(practice_sessions::user_id.eq(current_user_id))
+        .filter(tempo_entries::tempo_entry_id.eq(tempo_entry_id))
+        .first(&mut conn)
+        .optional())?;
+
+    let rows_deleted = match tempo_entry_id {
+        Some(tempo_entry_id) => map_backend_err!(diesel::delete(
+            tempo_entries::table.find(tempo_entry_id)
+        )
+        .execute(&mut conn))?,
+        None => 0,
+    };
+
+    Ok(Json(
+        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted }),
+    ))
+}
+
+#[derive(Serialize)]
+struct RepertoireEntryWithPiece {
+    #[serde(flatten)]
+    entry: RepertoireEntry,
+    piece: Piece,
+}
+
+fn load_repertoire_entry(
+    conn: &mut PgConnection,
+    user_id: i32,
+    piece_id: i32,
+) -> Result<RepertoireEntryWithPiece, AppError> {
+    let (entry, piece) = map_backend_err!(repertoire::table
+        .inner_join(pieces::table.inner_join(composers::table))
+        .select((RepertoireEntry::as_select(), Piece::as_select()))
+        .filter(repertoire::user_id.eq(user_id))
+        .filter(repertoire::piece_id.eq(piece_id))
+        .first::<(RepertoireEntry, Piece)>(conn)
+        .optional())?
+    .ok_or(AppError::NotFound(
+        "That piece is not in your repertoire".to_owned(),
+    ))?;
+
+    Ok(RepertoireEntryWithPiece { entry, piece })
+}
+
+#[derive(Deserialize)]
+struct GetRepertoireQueryParams {
+    status: Option<RepertoireStatus>,
+    piece_id: Option<i32>,
+}
+
+// entries are listed by status, then title, so pieces being worked on come first
+async fn get_repertoire(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<GetRepertoireQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let mut query = repertoire::table
+        .inner_join(pieces::table.inner_join(composers::table))
+        .select((RepertoireEntry::as_select(), Piece::as_select()))
+        .filter(repertoire::user_id.eq(current_user_id))
+        .into_boxed();
+
+    if let Some(status) = query_params.status {
+        query = query.filter(repertoire::status.eq(status));
+    }
+
+    if let Some(piece_id) = query_params.piece_id {
+        query = query.filter(repertoire::piece_id.eq(piece_id));
+    }
+
+    let entries: Vec<(RepertoireEntry, Piece)> =
+        map_backend_err!(query.load::<(RepertoireEntry, Piece)>(&mut conn))?;
+
+    let mut repertoire: Vec<RepertoireEntryWithPiece> = entries
+        .into_iter()
+        .map(|(entry, piece)| RepertoireEntryWithPiece { entry, piece })
+        .collect();
+    repertoire.sort_by(|a, b| {
+        (a.entry.status, &a.piece.title, a.piece.piece_id).cmp(&(
+            b.entry.status,
+            &b.piece.title,
+            b.piece.piece_id,
+        ))
+    });
+
+    Ok(Json(json!({ "success": true, "repertoire": repertoire })))
+}
+
+#[derive(Deserialize)]
+struct RepertoireEntryData {
+    status: RepertoireStatus,
+    target_date: Option<NaiveDate>,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NewRepertoireEntryData {
+    piece_id: i32,
+    #[serde(flatten)]
+    data: RepertoireEntryData,
+}
+
+async fn create_repertoire_entry(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(entry_data): Json<NewRepertoireEntryData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    diesel::insert_into(repertoire::table)
+        .values(InsertableRepertoireEntry {
+            user_id: current_user_id,
+            piece_id: entry_data.piece_id,
+            status: entry_data.data.status,
+            target_date: entry_data.data.target_date,
+            notes: entry_data.data.notes,
+        })
+        .execute(&mut conn)
+        .map_err(|e| match e {
+            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                AppError::Conflict("That piece is already in your repertoire".to_string())
+            }
+            DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+                AppError::NotFound("Piece not found".to_string())
+            }
+            _ => AppError::BackendError(e.to_string()),
+        })?;
+
+    let inserted_entry = load_repertoire_entry(&mut conn, current_user_id, entry_data.piece_id)?;
+
+    Ok(Json(
+        json!({ "success": true, "repertoire_entry": inserted_entry }),
+    ))
+}
+
+async fn update_repertoire_entry(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(piece_id): Path<i32>,
+    Json(entry_data): Json<RepertoireEntryData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let rows_updated = map_backend_err!(diesel::update(
+        repertoire::table.find((current_user_id, piece_id))
+    )
+    .set((
+        InsertableRepertoireEntry {
+            user_id: current_user_id,
+            piece_id,
+            status: entry_data.status,
+            target_date: entry_data.target_date,
+            notes: entry_data.notes,
+        },
+        repertoire::updated_at.eq(diesel::dsl::now),
+    ))
+    .execute(&mut conn))?;
+
+    if rows_updated == 0 {
+        return Err(AppError::NotFound(
+            "That piece is not in your repertoire".to_owned(),
+        ));
+    }
+
+    let updated_entry = load_repertoire_entry(&mut conn, current_user_id, piece_id)?;
+
+    Ok(Json(
+        json!({ "success": true, "repertoire_entry": updated_entry }),
+    ))
+}
+
+async fn delete_repertoire_entry(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(piece_id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let rows_deleted = map_backend_err!(diesel::delete(
+        repertoire::table.find((current_user_id, piece_id))
+    )
+    .execute(&mut conn))?;
+
+    Ok(Json(
+        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted }),
+    ))
+}
+
+#[derive(Deserialize)]
+struct GoalsQueryParams {
+    today: Option<NaiveDate>, // defaults to the current date in the user's timezone
+}
+
+// each goal comes with its progress in the current period
+async fn get_goals(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    query_params: Query<GoalsQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let today = match query_params.today {
+        Some(today) => verify_today(today)?,
+        None => Utc::now().with_timezone(&timezone).date_naive(),
+    };
+
+    let practice_goals: Vec<PracticeGoal> = map_backend_err!(practice_goals::table
+        .filter(practice_goals::user_id.eq(current_user_id))
+        .order(practice_goals::goal_id)
+        .load::<PracticeGoal>(&mut conn))?;
+
+    let goals = map_backend_err!(practice_goals
+        .into_iter()
+        .map(|goal| goals::with_current_progress(&mut conn, goal, &timezone, today))
+        .collect::<QueryResult<Vec<goals::GoalWithProgress>>>())?;
+
+    Ok(Json(json!({ "success": true, "goals": goals })))
+}
+
+fn load_goal(
+    conn: &mut PgConnection,
+    user_id: i32,
+    goal_id: i32,
+) -> Result<PracticeGoal, AppError> {
+    map_backend_err!(practice_goals::table
+        .filter(practice_goals::goal_id.eq(goal_id))
+        .filter(practice_goals::user_id.eq(user_id))
+        .first::<PracticeGoal>(conn)
+        .optional())?
+    .ok_or(AppError::NotFound("Goal not found".to_owned()))
+}
+
+#[derive(Deserialize)]
+struct GoalHistoryQueryParams {
+    periods: Option<u32>, // how many periods back to go, at most back to the goal's creation
+    today: Option<NaiveDate>,
+}
+
+async fn get_goal_history(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(goal_id): Path<i32>,
+    query_params: Query<GoalHistoryQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let num_periods = query_params.periods.unwrap_or(DEFAULT_GOAL_HISTORY_PERIODS);
+    if num_periods == 0 || num_periods > MAX_GOAL_HISTORY_PERIODS {
+        return Err(AppError::ClientError(
+            "Invalid value for periods".to_owned(),
+        ));
+    }
+
+    let mut conn = get_db_conn!(state)?;
+
+    let goal = load_goal(&mut conn, current_user_id, goal_id)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let today = match query_params.today {
+        Some(today) => verify_today(today)?,
+        None => Utc::now().with_timezone(&timezone).date_naive(),
+    };
+
+    let history = map_backend_err!(goals::goal_progress(
+        &mut conn,
+        &goal,
+        &timezone,
+        today,
+        num_periods
+    ))?;
+    let periods_completed = history.iter().filter(|period| period.completed).count();
+
+    Ok(Json(json!({
+        "success": true,
+        "goal": goal,
+        "history": history,
+        "periods_completed": periods_completed
+    })))
+}
+
+#[derive(Deserialize)]
+struct GoalData {
+    metric: GoalMetric,
+    period: Period,
+    target: i32,
+    piece_id: Option<i32>,
+    instrument_id: Option<i32>,
+}
+
+impl GoalData {
+    fn make_insertable(self, user_id: i32) -> Result<InsertablePracticeGoal, AppError> {
+        goals::validate_goal(self.metric, self.period, self.target)?;
+
+        Ok(InsertablePracticeGoal {
+            user_id,
+            metric: self.metric,
+            period: self.period,
+            target: self.target,
+            piece_id: self.piece_id,
+            instrument_id: self.instrument_id,
+        })
+    }
+}
+
+fn map_goal_err(e: Error) -> AppError {
+    match e {
+        DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+            AppError::NotFound("Piece not found".to_string())
+        }
+        _ => AppError::BackendError(e.to_string()),
+    }
+}
+
+async fn create_goal(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(goal_data): Json<GoalData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let new_goal = goal_data.make_insertable(current_user_id)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    if let Some(instrument_id) = new_goal.instrument_id {
+        load_instrument(&mut conn, current_user_id, instrument_id)?;
+    }
+
+    let inserted_goal: PracticeGoal = diesel::insert_into(practice_goals::table)
+        .values(new_goal)
+        .get_result(&mut conn)
+        .map_err(map_goal_err)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let today = Utc::now().with_timezone(&timezone).date_naive();
+
+    let goal = map_backend_err!(goals::with_current_progress(
+        &mut conn,
+        inserted_goal,
+        &timezone,
+        today
+    ))?;
+
+    Ok(Json(json!({ "success": true, "goal": goal })))
+}
+
+async fn update_goal(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(goal_id): Path<i32>,
+    Json(goal_data): Json<GoalData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let updated_goal = goal_data.make_insertable(current_user_id)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let _goal = load_goal(&mut conn, current_user_id, goal_id)?;
+
+    if let Some(instrument_id) = updated_goal.instrument_id {
+        load_instrument(&mut conn, current_user_id, instrument_id)?;
+    }
+
+    let updated_goal: PracticeGoal =
+        diesel::update(practice_goals::table.filter(practice_goals::goal_id.eq(goal_id)))
+            .set(updated_goal)
+            .get_result(&mut conn)
+            .map_err(map_goal_err)?;
+
+    let timezone = get_user_timezone(&mut conn, current_user_id)?;
+    let today = Utc::now().with_timezone(&timezone).date_naive();
+
+    let goal = map_backend_err!(goals::with_current_progress(
+        &mut conn,
+        updated_goal,
+        &timezone,
+        today
+    ))?;
+
+    Ok(Json(json!({ "success": true, "goal": goal })))
+}
+
+async fn delete_goal(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(goal_id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let rows_deleted = map_backend_err!(diesel::delete(
+        practice_goals::table
+            .filter(practice_goals::goal_id.eq(goal_id))
+            .filter(practice_goals::user_id.eq(current_user_id)),
+    )
+    .execute(&mut conn))?;
+
+    Ok(Json(
+        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted }),
+    ))
+}
+
+fn map_instrument_err(e: Error) -> AppError {
+    match e {
+        Error::NotFound => AppError::NotFound("Instrument not found".to_owned()),
+        DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            AppError::Conflict("An instrument with that name already exists".to_string())
+        }
+        _ => AppError::BackendError(e.to_string()),
+    }
+}
+
+async fn get_instruments(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let instruments =
+        map_backend_err!(instruments_with_session_counts(&mut conn, current_user_id))?;
+
+    Ok(Json(json!({ "success": true, "instruments": instruments })))
+}
+
+async fn create_instrument(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(instrument_data): Json<NewInstrumentData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let new_instrument = instrument_data.make_insertable(current_user_id)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let inserted_instrument: Instrument = diesel::insert_into(instruments::table)
+        .values(new_instrument)
+        .get_result(&mut conn)
+        .map_err(map_instrument_err)?;
+
+    Ok(Json(
+        json!({ "success": true, "instrument": inserted_instrument }),
+    ))
+}
+
+// sessions refer to their instrument, so renaming one renames it across the user's history
+async fn update_instrument(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(instrument_id): Path<i32>,
+    Json(instrument_data): Json<NewInstrumentData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let updated_instrument = instrument_data.make_insertable(current_user_id)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let updated_instrument: Instrument = diesel::update(
+        instruments::table
+            .filter(instruments::instrument_id.eq(instrument_id))
+            .filter(instruments::user_id.eq(current_user_id)),
+    )
+    .set(updated_instrument)
+    .get_result(&mut conn)
+    .map_err(map_instrument_err)?;
+
+    Ok(Json(
+        json!({ "success": true, "instrument": updated_instrument }),
+    ))
+}
+
+// instruments still used by sessions, goals or a running timer have to be merged into another
+// one instead
+async fn delete_instrument(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Path(instrument_id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let rows_deleted = conn.transaction::<_, AppError, _>(|conn| {
+        let instrument = load_instrument(conn, current_user_id, instrument_id)?;
+
+        let in_use: bool = diesel::select(diesel::dsl::exists(
+            practice_sessions::table
+                .filter(practice_sessions::instrument_id.eq(instrument.instrument_id)),
+        ))
+        .get_result(conn)?;
+        let in_goals: bool = diesel::select(diesel::dsl::exists(
+            practice_goals::table
+                .filter(practice_goals::instrument_id.eq(instrument.instrument_id)),
+        ))
+        .get_result(conn)?;
+        let in_timer: bool = diesel::select(diesel::dsl::exists(
+            practice_timers::table
+                .filter(practice_timers::instrument_id.eq(instrument.instrument_id)),
+        ))
+        .get_result(conn)?;
+        if in_use || in_goals || in_timer {
+            return Err(AppError::Conflict(
+                "Instrument is still used by practice sessions, goals or a practice timer"
+                    .to_owned(),
+            ));
+        }
+
+        Ok(diesel::delete(instruments::table.find(instrument.instrument_id)).execute(conn)?)
+    })?;
+
+    Ok(Json(
+        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted }),
+    ))
+}
+
+#[derive(Deserialize)]
+struct MergeInstrumentsData {
+    target_instrument_id: i32,
+    source_instrument_ids: Vec<i32>,
+}
+
+async fn merge_instruments(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(merge_data): Json<MergeInstrumentsData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let merge_summary = practice_app::instruments::merge_instruments(
+        &mut conn,
+        current_user_id,
+        merge_data.target_instrument_id,
+        &merge_data.source_instrument_ids,
+    )?;
+
+    Ok(Json(json!({ "success": true, "merge": merge_summary })))
+}
+
+async fn get_timer(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    // no timer isn't an error, it just means nothing is in progress
+    let timer_state = conn.transaction::<_, AppError, _>(|conn| {
+        practice_timers::table
+            .find(current_user_id)
+            .first::<PracticeTimer>(conn)
+            .optional()?
+            .map(|timer| timer::timer_state(conn, timer, Utc::now()))
+            .transpose()
+    })?;
+
+    Ok(Json(json!({ "success": true, "timer": timer_state })))
+}
+
+#[derive(Deserialize)]
+struct StartTimerData {
+    instrument: String, // instrument name, created for the user if they don't have it yet
+    piece_id: Option<i32>,
+}
+
+// each user can only have one timer running at a time
+async fn start_timer(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(timer_data): Json<StartTimerData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timer_state = conn.transaction::<_, AppError, _>(|conn| {
+        let instrument = get_or_create_instrument(conn, current_user_id, &timer_data.instrument)?;
+
+        let now = Utc::now();
+        let timer = timer::start_timer(
+            conn,
+            current_user_id,
+            instrument.instrument_id,
+            timer_data.piece_id,
+            now,
+        )?;
+
+        timer::timer_state(conn, timer, now)
+    })?;
+
+    Ok(Json(json!({ "success": true, "timer": timer_state })))
+}
+
+async fn pause_timer(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timer_state = conn.transaction::<_, AppError, _>(|conn| {
+        let now = Utc::now();
+        let timer = timer::load_timer(conn, current_user_id)?;
+        let timer = timer::pause_timer(conn, &timer, now)?;
+
+        timer::timer_state(conn, timer, now)
+    })?;
+
+    Ok(Json(json!({ "success": true, "timer": timer_state })))
+}
+
+async fn resume_timer(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let timer_state = conn.transaction::<_, AppError, _>(|conn| {
+        let now = Utc::now();
+        let timer = timer::load_timer(conn, current_user_id)?;
+        let timer = timer::resume_timer(conn, &timer, now)?;
+
+        timer::timer_state(conn, timer, now)
+    })?;
+
+    Ok(Json(json!({ "success": true, "timer": timer_state })))
+}
+
+#[derive(Deserialize)]
+struct SwitchTimerPieceData {
+    piece_id: Option<i32>, // null to carry on without a piece
+}
+
+async fn switch_timer_piece(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(switch_data): Json<SwitchTimerPieceData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let mut conn = get_db_conn!(state)?;
 
-    // get the pieces practiced in the above practice sessions
-    let pieces_practiced: Vec<Vec<(PiecePracticedMapping, Piece)>> =
-        map_backend_err!(PiecePracticedMapping::belonging_to(&practice_sessions)
-            .inner_join(pieces::table)
-            .load(&mut conn))?
-        .grouped_by(&practice_sessions);
+    let timer_state = conn.transaction::<_, AppError, _>(|conn| {
+        let now = Utc::now();
+        let timer = timer::load_timer(conn, current_user_id)?;
+        timer::switch_piece(conn, &timer, switch_data.piece_id, now)?;
 
-    // join together the practice sessions with the pieces practiced in each
-    let practice_sessions: Vec<PracticeSessionWithPieces> = practice_sessions
-        .into_iter()
-        .zip(pieces_practiced)
-        .map(|(practice_session, pieces_practiced)| {
-            PracticeSessionWithPieces::new(
-                practice_session,
-                pieces_practiced
-                    .into_iter()
-                    .map(|(_, piece)| piece)
-                    .collect(),
-            )
-        })
-        .collect();
+        timer::timer_state(conn, timer, now)
+    })?;
 
-    Ok(Json(
-        json!({"success": true, "practice_sessions": practice_sessions}),
-    ))
+    Ok(Json(json!({ "success": true, "timer": timer_state })))
 }
 
-async fn create_practice_session(
+#[derive(Deserialize)]
+struct StopTimerData {
+    notes: Option<String>,
+    rating: Option<u32>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+// saves the timer as a practice session starting when the timer was started, with the time
+// spent on each piece switched to, then discards the timer
+async fn stop_timer(
     State(state): State<Arc<AppState>>,
     session: ReadableSession,
-    Json(practice_session_data): Json<NewPracticeSessionData>,
+    Json(stop_data): Json<StopTimerData>,
 ) -> Result<Json<Value>, AppError> {
     let current_user_id = get_user_id!(session)?;
 
     let mut conn = get_db_conn!(state)?;
 
-    let inserted_practice_session: PracticeSession = diesel::insert_into(practice_sessions::table)
-        .values(practice_session_data.make_insertable(current_user_id)?)
-        .get_result(&mut conn)
-        .map_err(|e| match e {
-            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
-                AppError::Conflict("A practice session at that time already exists".to_string())
-            }
-            _ => AppError::BackendError(e.to_string()),
-        })?;
+    let inserted = conn.transaction::<_, AppError, _>(|conn| {
+        let timer = timer::load_timer(conn, current_user_id)?;
+        let elapsed_secs = timer::elapsed_secs(&timer, Utc::now());
 
-    let pieces_practiced_mappings: Vec<PiecePracticedMapping> = practice_session_data
-        .pieces_practiced
-        .iter()
-        .map(|piece| PiecePracticedMapping {
-            practice_session_id: inserted_practice_session.practice_session_id,
-            piece_id: piece.piece_id,
-        })
-        .collect();
+        if elapsed_secs < 60 {
+            return Err(AppError::ClientError(
+                "Practice timers must run for at least a minute to be saved".to_owned(),
+            ));
+        }
 
-    let pieces_practiced_inserted = diesel::insert_into(pieces_practiced::table)
-        .values(pieces_practiced_mappings)
-        .get_results::<PiecePracticedMapping>(&mut conn)
-        .map_err(|e| match e {
-            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
-                AppError::Conflict("That piece practiced mapping already exists".to_string())
-            }
-            _ => AppError::BackendError(e.to_string()),
-        })?;
+        let instrument = load_instrument(conn, current_user_id, timer.instrument_id)?;
+        let segments = timer::load_segments(conn, current_user_id)?;
+
+        let practice_session_data = NewPracticeSessionData {
+            start_datetime: timer.started_at,
+            duration_mins: (elapsed_secs / 60).unsigned_abs(),
+            instrument: instrument.name,
+            pieces_practiced: timer::make_pieces_practiced(&segments, elapsed_secs),
+            notes: stop_data.notes,
+            rating: stop_data.rating,
+            tags: Some(stop_data.tags),
+        };
+
+        let inserted = insert_practice_session(conn, current_user_id, &practice_session_data)?;
+
+        timer::delete_timer(conn, current_user_id)?;
+
+        Ok(inserted)
+    })?;
 
     Ok(Json(json!({
         "success": true,
-        "practice_session": inserted_practice_session,
-        "pieces_practiced": pieces_practiced_inserted
+        "practice_session": inserted.practice_session,
+        "instrument": inserted.instrument,
+        "pieces_practiced": inserted.pieces_practiced,
+        "tempo_entries": inserted.tempo_entries,
+        "tags": inserted.tags
     })))
 }
 
-async fn delete_practice_session(
+// discards the timer without saving anything
+async fn delete_timer(
     State(state): State<Arc<AppState>>,
     session: ReadableSession,
-    Path(practice_session_id): Path<i32>,
 ) -> Result<Json<Value>, AppError> {
     let current_user_id = get_user_id!(session)?;
 
     let mut conn = get_db_conn!(state)?;
 
-    // delete the pieces practiced mappings that link to this practice session first...
-    let _practice_session_id: i32 =
-        verify_practice_session_ownership(&mut conn, practice_session_id, current_user_id)?;
-
-    let pieces_practiced_deleted: usize = map_backend_err!(diesel::delete(
-        pieces_practiced::table
-            .filter(pieces_practiced::practice_session_id.eq(practice_session_id))
-    )
-    .execute(&mut conn))?;
-
-    // ...then delete practice session itself
-    let rows_deleted: usize = map_backend_err!(diesel::delete(
-        practice_sessions::table
-            .filter(practice_sessions::user_id.eq(current_user_id))
-            .filter(practice_sessions::practice_session_id.eq(practice_session_id))
-    )
-    .execute(&mut conn))?;
+    let rows_deleted =
+        conn.transaction::<_, AppError, _>(|conn| Ok(timer::delete_timer(conn, current_user_id)?))?;
 
     Ok(Json(
-        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted, "pieces_practiced_mappings_deleted": pieces_practiced_deleted }),
+        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted }),
     ))
 }
 
 #[derive(Deserialize)]
-struct GetPiecesQueryParams {
-    piece_id: Option<i32>,
-    title: Option<String>,    // match containing
-    composer: Option<String>, // match containing
+struct TagData {
+    name: String,
 }
 
-async fn get_pieces(
-    State(state): State<Arc<AppState>>,
-    query_params: Query<GetPiecesQueryParams>,
-) -> Result<Json<Value>, AppError> {
-    let mut conn = get_db_conn!(state)?;
-
-    let mut query = pieces::table.into_boxed();
-
-    if let Some(piece_id) = query_params.piece_id {
-        query = query.filter(pieces::piece_id.eq(piece_id));
-    }
-
-    if let Some(title) = &query_params.title {
-        query = query.filter(pieces::title.ilike(format!("%{}%", title)));
-    }
-
-    if let Some(composer) = &query_params.composer {
-        query = query.filter(pieces::composer.ilike(format!("%{}%", composer)));
+fn map_tag_err(e: Error) -> AppError {
+    match e {
+        Error::NotFound => AppError::NotFound("Tag not found".to_owned()),
+        DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            AppError::Conflict("A tag with that name already exists".to_string())
+        }
+        _ => AppError::BackendError(e.to_string()),
     }
-
-    let pieces: Vec<Piece> = map_backend_err!(query.load::<Piece>(&mut conn))?;
-
-    Ok(Json(json!({ "success": true, "pieces": pieces })))
 }
 
-async fn create_piece(
+async fn get_tags(
     State(state): State<Arc<AppState>>,
     session: ReadableSession,
-    Json(new_piece): Json<InsertablePiece>,
 ) -> Result<Json<Value>, AppError> {
-    // don't need user id to insert a piece into db, but this checks to make sure user is logged in
-    // (don't want users to be able to create pieces without being logged in)
-    let _current_user_id = get_user_id!(session)?;
+    let current_user_id = get_user_id!(session)?;
 
     let mut conn = get_db_conn!(state)?;
 
-    let inserted_piece: Piece = diesel::insert_into(pieces::table)
-        .values(new_piece)
-        .get_result(&mut conn)
-        .map_err(|e| match e {
-            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
-                AppError::Conflict("That piece is already registered in the database".to_string())
-            }
-            _ => AppError::BackendError(e.to_string()),
-        })?;
+    let tags = map_backend_err!(tags_with_session_counts(&mut conn, current_user_id))?;
 
-    Ok(Json(json!({ "success": true, "piece": inserted_piece })))
+    Ok(Json(json!({ "success": true, "tags": tags })))
 }
 
-async fn delete_piece(
+async fn create_tag(
     State(state): State<Arc<AppState>>,
-    Path(piece_id): Path<i32>,
+    session: ReadableSession,
+    Json(tag_data): Json<TagData>,
 ) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let name = normalize_tag_name(&tag_data.name)?;
+
     let mut conn = get_db_conn!(state)?;
 
-    let rows_deleted: usize = map_backend_err!(diesel::delete(
-        pieces::table.filter(pieces::piece_id.eq(piece_id))
-    )
-    .execute(&mut conn))?;
+    let inserted_tag: Tag = diesel::insert_into(tags::table)
+        .values((tags::user_id.eq(current_user_id), tags::name.eq(name)))
+        .get_result(&mut conn)
+        .map_err(map_tag_err)?;
 
-    Ok(Json(
-        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted }),
-    ))
+    Ok(Json(json!({ "success": true, "tag": inserted_tag })))
 }
 
-async fn create_piece_practiced(
+// renaming a tag renames it on every session it's attached to
+async fn update_tag(
     State(state): State<Arc<AppState>>,
     session: ReadableSession,
-    Json(piece_practiced_mapping): Json<PiecePracticedMapping>,
+    Path(tag_id): Path<i32>,
+    Json(tag_data): Json<TagData>,
 ) -> Result<Json<Value>, AppError> {
     let current_user_id = get_user_id!(session)?;
 
-    let mut conn = get_db_conn!(state)?;
+    let name = normalize_tag_name(&tag_data.name)?;
 
-    // verify that the practice session in the mapping belongs to the current user
-    let _practice_session_id = verify_practice_session_ownership(
-        &mut conn,
-        piece_practiced_mapping.practice_session_id,
-        current_user_id,
-    )?;
+    let mut conn = get_db_conn!(state)?;
 
-    let inserted_mapping = diesel::insert_into(pieces_practiced::table)
-        .values(piece_practiced_mapping)
-        .execute(&mut conn)
-        .map_err(|e| match e {
-            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
-                AppError::Conflict("Entry already exists".to_string())
-            }
-            DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
-                // "piece not found" must be the case because:
-                // 1: practice session existence is verified above using verify_practice_session_ownership!()
-                // 2: piece_id is the only foreign key remaining
-                AppError::ClientError("Piece not found".to_string())
-            }
-            _ => AppError::BackendError(e.to_string()),
-        })?;
+    let updated_tag: Tag = diesel::update(
+        tags::table
+            .filter(tags::tag_id.eq(tag_id))
+            .filter(tags::user_id.eq(current_user_id)),
+    )
+    .set(tags::name.eq(name))
+    .get_result(&mut conn)
+    .map_err(map_tag_err)?;
 
-    Ok(Json(
-        json!({ "success": true, "piece_practiced": inserted_mapping }),
-    ))
+    Ok(Json(json!({ "success": true, "tag": updated_tag })))
 }
 
-async fn delete_piece_practiced(
+// removes the tag from every session it's attached to; the sessions themselves are kept
+async fn delete_tag(
     State(state): State<Arc<AppState>>,
     session: ReadableSession,
-    Path((practice_session_id, piece_id)): Path<(i32, i32)>,
+    Path(tag_id): Path<i32>,
 ) -> Result<Json<Value>, AppError> {
     let current_user_id = get_user_id!(session)?;
 
     let mut conn = get_db_conn!(state)?;
 
-    // verify that the practice session in the mapping belongs to the current user
-    let _practice_session_id =
-        verify_practice_session_ownership(&mut conn, practice_session_id, current_user_id)?;
+    let (sessions_untagged, rows_deleted) = conn.transaction::<_, AppError, _>(|conn| {
+        let tag: Option<Tag> = tags::table
+            .filter(tags::tag_id.eq(tag_id))
+            .filter(tags::user_id.eq(current_user_id))
+            .first(conn)
+            .optional()?;
 
-    let rows_deleted = map_backend_err!(diesel::delete(
-        pieces_practiced::table
-            .filter(pieces_practiced::piece_id.eq(piece_id))
-            .filter(pieces_practiced::practice_session_id.eq(practice_session_id)),
-    )
-    .execute(&mut conn))?;
+        let Some(tag) = tag else {
+            return Ok((0, 0));
+        };
 
-    Ok(Json(
-        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted }),
-    ))
+        let sessions_untagged = diesel::delete(
+            practice_session_tags::table.filter(practice_session_tags::tag_id.eq(tag.tag_id)),
+        )
+        .execute(conn)?;
+
+        let rows_deleted = diesel::delete(tags::table.find(tag.tag_id)).execute(conn)?;
+
+        Ok((sessions_untagged, rows_deleted))
+    })?;
+
+    Ok(Json(json!({
+        "success": rows_deleted > 0,
+        "num_deleted": rows_deleted,
+        "sessions_untagged": sessions_untagged
+    })))
 }
 
 async fn create_user(
@@ -402,6 +2447,13 @@ async fn login(
         credentials.password.as_bytes()
     ))?;
 
+    // only reveal that the account is disabled to someone who knows its password
+    if login_success && user.disabled_at.is_some() {
+        return Err(AppError::Forbidden(
+            "This account has been disabled".to_owned(),
+        ));
+    }
+
     if login_success {
         session.regenerate(); // this is supposed to make it more secure or something
         map_backend_err!(session.insert("user_id", user.user_id))?;
@@ -409,7 +2461,9 @@ async fn login(
         Ok(Json(json!({
             "success": login_success,
             "user_id": user.user_id,
-            "user_name": user.user_name
+            "user_name": user.user_name,
+            "timezone": user.timezone,
+            "role": user.role
         }))
         .into_response())
     } else {
@@ -417,6 +2471,33 @@ async fn login(
     }
 }
 
+#[derive(Deserialize)]
+struct TimezoneData {
+    timezone: String, // IANA name, e.g. "America/New_York"
+}
+
+async fn update_timezone(
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(timezone_data): Json<TimezoneData>,
+) -> Result<Json<Value>, AppError> {
+    let current_user_id = get_user_id!(session)?;
+
+    let timezone = parse_timezone(&timezone_data.timezone)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let rows_updated: usize = map_backend_err!(diesel::update(
+        users::table.filter(users::user_id.eq(current_user_id))
+    )
+    .set(users::timezone.eq(timezone.name()))
+    .execute(&mut conn))?;
+
+    Ok(Json(
+        json!({ "success": rows_updated > 0, "timezone": timezone.name() }),
+    ))
+}
+
 async fn logout(mut session: WritableSession) -> Result<Response, AppError> {
     let _current_user_id = get_user_id!(session)?;
 
@@ -425,6 +2506,257 @@ async fn logout(mut session: WritableSession) -> Result<Response, AppError> {
     Ok(Json(json!({"success": true})).into_response())
 }
 
+trait RequiredRole {
+    const ROLE: Role;
+}
+
+struct Admin;
+
+impl RequiredRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+// the logged in user's id, for handlers that need the user to have at least role R; unlike
+// get_user_id! this checks the database, so role changes and disabling apply immediately
+struct Authorized<R: RequiredRole> {
+    user_id: i32,
+    _role: PhantomData<R>,
+}
+
+#[async_trait]
+impl<R: RequiredRole> FromRequestParts<Arc<AppState>> for Authorized<R> {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let user_id = {
+            let session = match ReadableSession::from_request_parts(parts, state).await {
+                Ok(session) => session,
+                Err(infallible) => match infallible {},
+            };
+            get_user_id!(session)?
+        };
+
+        let mut conn = get_db_conn!(state)?;
+
+        // the session can outlive its user, who is then treated as logged out
+        let (role, disabled_at): (Role, Option<DateTime<Utc>>) = map_backend_err!(users::table
+            .select((users::role, users::disabled_at))
+            .filter(users::user_id.eq(user_id))
+            .first(&mut conn)
+            .optional())?
+        .ok_or(AppError::Unauthorized)?;
+
+        if disabled_at.is_some() || role < R::ROLE {
+            return Err(AppError::Forbidden(format!(
+                "This requires the {} role",
+                R::ROLE
+            )));
+        }
+
+        Ok(Self {
+            user_id,
+            _role: PhantomData,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct UsersPageParams {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    cursor: Option<String>, // next_cursor from the previous page
+}
+
+// users are listed by name
+async fn admin_get_users(
+    State(state): State<Arc<AppState>>,
+    _admin: Authorized<Admin>,
+    page_params: Query<UsersPageParams>,
+) -> Result<Json<Value>, AppError> {
+    let limit = page_size(page_params.limit)?;
+    let offset = offset(page_params.offset)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let total_count: i64 = map_backend_err!(users::table.count().get_result(&mut conn))?;
+
+    let mut query = users::table.select(UserSummary::as_select()).into_boxed();
+    query = order_after_cursor!(
+        query,
+        users::user_name,
+        users::user_id,
+        SortOrder::Asc,
+        page_params
+            .cursor
+            .as_deref()
+            .map(Cursor::<String>::decode)
+            .transpose()?
+    );
+
+    let users: Vec<UserSummary> = map_backend_err!(query
+        .limit(limit)
+        .offset(offset)
+        .load::<UserSummary>(&mut conn))?;
+
+    let next_cursor: Option<String> =
+        users
+            .last()
+            .filter(|_| users.len() as i64 == limit)
+            .map(|last| {
+                Cursor {
+                    value: last.user_name.clone(),
+                    id: last.user_id,
+                }
+                .encode()
+            });
+
+    Ok(Json(json!({
+        "success": true,
+        "users": users,
+        "total_count": total_count,
+        "next_cursor": next_cursor
+    })))
+}
+
+#[derive(Deserialize)]
+struct UpdateUserData {
+    role: Option<Role>,
+    disabled: Option<bool>,
+}
+
+async fn admin_update_user(
+    State(state): State<Arc<AppState>>,
+    admin: Authorized<Admin>,
+    Path(user_id): Path<i32>,
+    Json(user_data): Json<UpdateUserData>,
+) -> Result<Json<Value>, AppError> {
+    // stops the last admin from locking everyone out of the admin endpoints
+    if user_id == admin.user_id {
+        return Err(AppError::Forbidden(
+            "Admins cannot change their own role or disable themselves".to_owned(),
+        ));
+    }
+
+    let mut conn = get_db_conn!(state)?;
+
+    let updated_user: UserSummary = conn.transaction::<_, AppError, _>(|conn| {
+        if let Some(role) = user_data.role {
+            diesel::update(users::table.filter(users::user_id.eq(user_id)))
+                .set(users::role.eq(role))
+                .execute(conn)?;
+        }
+
+        match user_data.disabled {
+            Some(true) => {
+                // only the first disable is recorded, so disabling again keeps the original time
+                diesel::update(
+                    users::table
+                        .filter(users::user_id.eq(user_id))
+                        .filter(users::disabled_at.is_null()),
+                )
+                .set(users::disabled_at.eq(Utc::now()))
+                .execute(conn)?;
+                session_store::delete_user_sessions(conn, user_id)?;
+            }
+            Some(false) => {
+                diesel::update(users::table.filter(users::user_id.eq(user_id)))
+                    .set(users::disabled_at.eq(None::<DateTime<Utc>>))
+                    .execute(conn)?;
+            }
+            None => {}
+        }
+
+        users::table
+            .select(UserSummary::as_select())
+            .filter(users::user_id.eq(user_id))
+            .first::<UserSummary>(conn)
+            .map_err(|e| match e {
+                Error::NotFound => AppError::NotFound("User not found".to_owned()),
+                _ => AppError::BackendError(e.to_string()),
+            })
+    })?;
+
+    Ok(Json(json!({ "success": true, "user": updated_user })))
+}
+
+async fn admin_delete_user(
+    State(state): State<Arc<AppState>>,
+    admin: Authorized<Admin>,
+    Path(user_id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    if user_id == admin.user_id {
+        return Err(AppError::Forbidden(
+            "Admins cannot delete themselves".to_owned(),
+        ));
+    }
+
+    let mut conn = get_db_conn!(state)?;
+
+    let rows_deleted = delete_user(&mut conn, user_id)?;
+
+    Ok(Json(
+        json!({ "success": rows_deleted > 0, "num_deleted": rows_deleted }),
+    ))
+}
+
+const DEFAULT_DUPLICATE_SIMILARITY: f64 = 0.6;
+
+#[derive(Deserialize)]
+struct DuplicatePiecesQueryParams {
+    piece_id: Option<i32>,       // only find duplicates of this piece
+    min_similarity: Option<f64>, // between 0 and 1
+    limit: Option<i64>,
+}
+
+async fn admin_get_duplicate_pieces(
+    State(state): State<Arc<AppState>>,
+    _admin: Authorized<Admin>,
+    query_params: Query<DuplicatePiecesQueryParams>,
+) -> Result<Json<Value>, AppError> {
+    let min_similarity = query_params
+        .min_similarity
+        .unwrap_or(DEFAULT_DUPLICATE_SIMILARITY);
+    if !(0.0..=1.0).contains(&min_similarity) {
+        return Err(AppError::ClientError(
+            "min_similarity must be between 0 and 1".to_owned(),
+        ));
+    }
+
+    let limit = page_size(query_params.limit)?;
+
+    let mut conn = get_db_conn!(state)?;
+
+    let duplicates =
+        duplicates::find_duplicate_pieces(&mut conn, query_params.piece_id, min_similarity, limit)?;
+
+    Ok(Json(json!({ "success": true, "duplicates": duplicates })))
+}
+
+#[derive(Deserialize)]
+struct MergePiecesData {
+    target_piece_id: i32,
+    source_piece_ids: Vec<i32>,
+}
+
+async fn admin_merge_pieces(
+    State(state): State<Arc<AppState>>,
+    _admin: Authorized<Admin>,
+    Json(merge_data): Json<MergePiecesData>,
+) -> Result<Json<Value>, AppError> {
+    let mut conn = get_db_conn!(state)?;
+
+    let merge_summary = duplicates::merge_pieces(
+        &mut conn,
+        merge_data.target_piece_id,
+        &merge_data.source_piece_ids,
+    )?;
+
+    Ok(Json(json!({ "success": true, "merge": merge_summary })))
+}
+
 async fn logger_middleware<B>(request: Request<B>, next: Next<B>) -> Response {
     let method = request.method().clone();
     let uri = request.uri().clone();
@@ -448,24 +2780,31 @@ async fn main() {
     env::set_var("RUST_LOG", "info");
     env_logger::init();
 
-    let store = MemoryStore::new();
-    info!("Initialized memory store for sessions");
+    dotenv().expect(".env should load");
+
+    let db = get_connection_pool();
+    info!("Initialized database connection");
 
-    let mut secret = [0u8; 64];
-    rand::thread_rng().fill_bytes(&mut secret);
+    let store = PgSessionStore::new(db.clone());
+    store.spawn_cleanup_task(SESSION_CLEANUP_PERIOD);
+    info!("Initialized database store for sessions");
 
-    let session_layer = SessionLayer::new(store, &secret);
+    // the secret must stay the same across restarts and instances, otherwise existing
+    // session cookies can no longer be verified
+    let secret = env::var("SESSION_SECRET").expect("SESSION_SECRET env var should be set");
+    if secret.len() < 64 {
+        panic!("SESSION_SECRET should be at least 64 bytes long");
+    }
 
-    let shared_state = Arc::new(AppState {
-        db: get_connection_pool(),
-    });
-    info!("Initialized database connection");
+    let session_layer =
+        SessionLayer::new(store, secret.as_bytes()).with_session_ttl(Some(SESSION_TTL));
+
+    let shared_state = Arc::new(AppState { db });
 
-    dotenv().expect(".env should load");
     let frontend_url = env::var("FRONTEND_URL").expect("FRONTEND_URL env var should be set");
 
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_headers([CONTENT_TYPE])
         .allow_credentials(true)
         .allow_origin(frontend_url.parse::<HeaderValue>().unwrap());
@@ -473,24 +2812,115 @@ async fn main() {
     let app = Router::new()
         .route("/api/get_practice_sessions", get(get_practice_sessions))
         .route("/api/get_pieces", get(get_pieces))
+        .route("/api/search_pieces", get(search_pieces))
+        .route("/api/get_composers", get(get_composers))
+        .route("/api/search_composers", get(search_composers))
         .route(
             "/api/create_practice_session",
             post(create_practice_session),
         )
+        .route(
+            "/api/update_practice_session/:practice_session_id",
+            put(update_practice_session),
+        )
+        .route(
+            "/api/export_practice_sessions",
+            get(export_practice_sessions),
+        )
+        .route(
+            "/api/import_practice_sessions",
+            post(import_practice_sessions),
+        )
+        .route("/api/import_calendar", post(import_calendar))
         .route("/api/create_piece", post(create_piece))
         .route("/api/create_piece_practiced", post(create_piece_practiced))
         .route(
             "/api/delete_practice_session/:practice_session_id",
             delete(delete_practice_session),
         )
+        .route("/api/update_piece/:piece_id", put(update_piece))
         .route("/api/delete_piece/:piece_id", delete(delete_piece))
         .route(
             "/api/delete_piece_practiced/:practice_session_id_to_delete/:piece_id_to_delete",
             delete(delete_piece_practiced),
         )
+        .route("/api/create_tempo_entry", post(create_tempo_entry))
+        .route(
+            "/api/delete_tempo_entry/:tempo_entry_id",
+            delete(delete_tempo_entry),
+        )
+        .route("/api/get_repertoire", get(get_repertoire))
+        .route(
+            "/api/create_repertoire_entry",
+            post(create_repertoire_entry),
+        )
+        .route(
+            "/api/update_repertoire_entry/:piece_id",
+            put(update_repertoire_entry),
+        )
+        .route(
+            "/api/delete_repertoire_entry/:piece_id",
+            delete(delete_repertoire_entry),
+        )
+        .route("/api/get_goals", get(get_goals))
+        .route("/api/get_goal_history/:goal_id", get(get_goal_history))
+        .route("/api/create_goal", post(create_goal))
+        .route("/api/update_goal/:goal_id", put(update_goal))
+        .route("/api/delete_goal/:goal_id", delete(delete_goal))
+        .route("/api/get_instruments", get(get_instruments))
+        .route("/api/create_instrument", post(create_instrument))
+        .route(
+            "/api/update_instrument/:instrument_id",
+            put(update_instrument),
+        )
+        .route(
+            "/api/delete_instrument/:instrument_id",
+            delete(delete_instrument),
+        )
+        .route("/api/merge_instruments", post(merge_instruments))
+        .route("/api/get_timer", get(get_timer))
+        .route("/api/start_timer", post(start_timer))
+        .route("/api/pause_timer", post(pause_timer))
+        .route("/api/resume_timer", post(resume_timer))
+        .route("/api/switch_timer_piece", post(switch_timer_piece))
+        .route("/api/stop_timer", post(stop_timer))
+        .route("/api/delete_timer", delete(delete_timer))
+        .route("/api/get_tags", get(get_tags))
+        .route("/api/create_tag", post(create_tag))
+        .route("/api/update_tag/:tag_id", put(update_tag))
+        .route("/api/delete_tag/:tag_id", delete(delete_tag))
+        .route("/api/stats/minutes_by_period", get(get_minutes_by_period))
+        .route(
+            "/api/stats/minutes_by_instrument",
+            get(get_minutes_by_instrument),
+        )
+        .route("/api/stats/minutes_by_piece", get(get_minutes_by_piece))
+        .route(
+            "/api/stats/minutes_by_composer",
+            get(get_minutes_by_composer),
+        )
+        .route("/api/stats/minutes_by_tag", get(get_minutes_by_tag))
+        .route("/api/stats/tempo_series", get(get_tempo_series))
+        .route("/api/stats/streaks", get(get_streaks))
         .route("/api/create_user", post(create_user))
         .route("/api/login", post(login))
         .route("/api/logout", get(logout))
+        .route("/api/update_timezone", put(update_timezone))
+        .route("/api/admin/get_users", get(admin_get_users))
+        .route("/api/admin/update_user/:user_id", put(admin_update_user))
+        .route("/api/admin/delete_user/:user_id", delete(admin_delete_user))
+        .route(
+            "/api/admin/get_duplicate_pieces",
+            get(admin_get_duplicate_pieces),
+        )
+        .route("/api/admin/merge_pieces", post(admin_merge_pieces))
+        .route(
+            "/api/admin/update_composer/:composer_id",
+            put(admin_update_composer),
+        )
+        .route("/api/calendar/:calendar_token", get(get_calendar))
+        .route("/api/create_calendar_token", post(create_calendar_token))
+        .route("/api/delete_calendar_token", delete(delete_calendar_token))
         .layer(session_layer)
         .layer(cors)
         .layer(middleware::from_fn(logger_middleware))
@@ -1,8 +1,75 @@
-use crate::schema::{pieces, pieces_practiced, practice_sessions, users};
+// Insertable structs that also derive AsChangeset treat None as null, so an update replaces
+// every field and leaving an optional field out of the request body clears it
+
+use crate::schema::{
+    composers, instruments, pieces, pieces_practiced, practice_goals, practice_session_tags,
+    practice_sessions, practice_timer_segments, practice_timers, repertoire, sessions, tags,
+    tempo_entries, users,
+};
+use crate::stats::Period;
 use chrono;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
 use diesel::prelude::*;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Varchar;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::io::Write;
+
+// roles are ordered, so a user has every permission of the roles below their own
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    AsExpression,
+    FromSqlRow,
+)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    User,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl ToSql<Varchar, Pg> for Role {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for Role {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"user" => Ok(Role::User),
+            b"admin" => Ok(Role::Admin),
+            other => Err(format!("Unknown role: {}", String::from_utf8_lossy(other)).into()),
+        }
+    }
+}
 
 #[derive(Queryable, Selectable, Serialize)]
 #[diesel(primary_key(user_id))]
@@ -12,28 +79,50 @@ pub struct User {
     pub user_id: i32,
     pub user_name: String,
     pub password_hash: String,
+    pub timezone: String,
+    pub calendar_token: Option<String>,
+    pub role: Role,
+    pub disabled_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl Display for User {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "({}) NAME: {} PASS: {}",
-            self.user_id, self.user_name, self.password_hash
-        )
+            "({}) NAME: {} PASS: {} TIMEZONE: {} ROLE: {}",
+            self.user_id, self.user_name, self.password_hash, self.timezone, self.role
+        )?;
+        if let Some(disabled_at) = self.disabled_at {
+            write!(f, " DISABLED: {disabled_at}")?;
+        }
+        Ok(())
     }
 }
 
+// what admins can see of other users, without their password hash or calendar token
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = users)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UserSummary {
+    pub user_id: i32,
+    pub user_name: String,
+    pub timezone: String,
+    pub role: Role,
+    pub disabled_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
 #[derive(Queryable, Selectable, Serialize, Identifiable)]
 #[diesel(primary_key(practice_session_id))]
 #[diesel(table_name = practice_sessions)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct PracticeSession {
     pub practice_session_id: i32,
-    pub start_datetime: chrono::NaiveDateTime,
+    pub start_datetime: chrono::DateTime<chrono::Utc>,
     pub duration_mins: i32,
-    pub instrument: String,
     pub user_id: i32,
+    pub notes: Option<String>,
+    pub rating: Option<i32>, // 1 to 5
+    pub instrument_id: i32,
 }
 
 impl Display for PracticeSession {
@@ -44,30 +133,89 @@ impl Display for PracticeSession {
             self.practice_session_id,
             self.start_datetime,
             self.duration_mins,
-            self.instrument,
+            self.instrument_id,
             self.user_id
-        )
+        )?;
+        if let Some(rating) = self.rating {
+            write!(f, " RATING: {rating}")?;
+        }
+        if let Some(notes) = &self.notes {
+            write!(f, " NOTES: {notes}")?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Insertable, Deserialize)]
+#[derive(Insertable, AsChangeset, Deserialize)]
 #[diesel(table_name = practice_sessions)]
+#[diesel(treat_none_as_null = true)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct InsertablePracticeSession {
-    pub start_datetime: chrono::NaiveDateTime,
+    pub start_datetime: chrono::DateTime<chrono::Utc>,
     pub duration_mins: i32,
-    pub instrument: String,
     pub user_id: i32,
+    pub notes: Option<String>,
+    pub rating: Option<i32>,
+    pub instrument_id: i32,
+}
+
+#[derive(Queryable, Selectable, Serialize, Identifiable)]
+#[diesel(table_name = composers)]
+#[diesel(primary_key(composer_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Composer {
+    pub composer_id: i32,
+    pub name: String,
+    pub sort_name: String,
+    pub birth_year: Option<i32>,
+    pub death_year: Option<i32>,
+    pub era: Option<String>,
+    pub open_opus_id: Option<i32>,
 }
 
-#[derive(Queryable, Selectable, Serialize, Identifiable, Deserialize)]
+impl Display for Composer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}) NAME: {}", self.composer_id, self.name)?;
+        if let Some(era) = &self.era {
+            write!(f, " ERA: {era}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Insertable, AsChangeset, Deserialize)]
+#[diesel(table_name = composers)]
+#[diesel(treat_none_as_null = true)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InsertableComposer {
+    pub name: String,
+    pub sort_name: String,
+    pub birth_year: Option<i32>,
+    pub death_year: Option<i32>,
+    pub era: Option<String>,
+    pub open_opus_id: Option<i32>,
+}
+
+// pieces are always selected joined with their composer, so they can be shown by name
+#[derive(Queryable, Selectable, Serialize, Identifiable)]
 #[diesel(table_name = pieces)]
 #[diesel(primary_key(piece_id))]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Piece {
     pub piece_id: i32,
     pub title: String,
+    #[diesel(select_expression = composers::name)]
+    #[diesel(select_expression_type = composers::name)]
     pub composer: String,
+    pub composer_id: i32,
+    pub created_by: Option<i32>,
+    pub catalogue_number: Option<String>,
+    pub key: Option<String>,
+    pub instrumentation: Option<String>,
+    pub genre: Option<String>,
+    pub parent_piece_id: Option<i32>, // set for movements, to the work they're part of
+    pub movement_number: Option<i32>,
+    pub open_opus_id: Option<i32>,
 }
 
 impl Display for Piece {
@@ -76,19 +224,39 @@ impl Display for Piece {
             f,
             "({}) TITLE: {} COMPOSER: {}",
             self.piece_id, self.title, self.composer
-        )
+        )?;
+        if let Some(catalogue_number) = &self.catalogue_number {
+            write!(f, " CATALOGUE: {catalogue_number}")?;
+        }
+        if let Some(parent_piece_id) = self.parent_piece_id {
+            write!(f, " MOVEMENT OF: {parent_piece_id}")?;
+        }
+        if let Some(created_by) = self.created_by {
+            write!(f, " CREATED BY: {created_by}")?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Insertable, Deserialize, PartialEq)]
+#[derive(Insertable, AsChangeset, PartialEq)]
 #[diesel(table_name = pieces)]
+#[diesel(treat_none_as_null = true)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct InsertablePiece {
     pub title: String,
-    pub composer: String,
+    pub composer_id: i32,
+    pub created_by: Option<i32>,
+    pub catalogue_number: Option<String>,
+    pub key: Option<String>,
+    pub instrumentation: Option<String>,
+    pub genre: Option<String>,
+    pub parent_piece_id: Option<i32>,
+    pub movement_number: Option<i32>,
 }
 
-#[derive(Queryable, Selectable, Serialize, Associations, Identifiable, Insertable, Deserialize)]
+#[derive(
+    Queryable, Selectable, Serialize, Associations, Identifiable, Insertable, Deserialize, Clone,
+)]
 #[diesel(primary_key(practice_session_id, piece_id))]
 #[diesel(belongs_to(PracticeSession))]
 #[diesel(belongs_to(Piece))]
@@ -97,6 +265,9 @@ pub struct InsertablePiece {
 pub struct PiecePracticedMapping {
     pub practice_session_id: i32,
     pub piece_id: i32,
+    pub duration_mins: Option<i32>,
+    pub start_offset_mins: Option<i32>,
+    pub notes: Option<String>,
 }
 
 impl Display for PiecePracticedMapping {
@@ -105,6 +276,433 @@ impl Display for PiecePracticedMapping {
             f,
             "PRACTICE SESSION: {} PIECE: {}",
             self.practice_session_id, self.piece_id
+        )?;
+        if let Some(duration_mins) = self.duration_mins {
+            write!(f, " DURATION: {duration_mins}")?;
+        }
+        if let Some(start_offset_mins) = self.start_offset_mins {
+            write!(f, " OFFSET: {start_offset_mins}")?;
+        }
+        if let Some(notes) = &self.notes {
+            write!(f, " NOTES: {notes}")?;
+        }
+        Ok(())
+    }
+}
+
+// how far along a user is with a piece in their repertoire, in the order pieces move through
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    AsExpression,
+    FromSqlRow,
+)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum RepertoireStatus {
+    Wishlist,
+    Learning,
+    Polishing,
+    PerformanceReady,
+    Retired,
+}
+
+impl RepertoireStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepertoireStatus::Wishlist => "wishlist",
+            RepertoireStatus::Learning => "learning",
+            RepertoireStatus::Polishing => "polishing",
+            RepertoireStatus::PerformanceReady => "performance_ready",
+            RepertoireStatus::Retired => "retired",
+        }
+    }
+}
+
+impl Display for RepertoireStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl ToSql<Varchar, Pg> for RepertoireStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for RepertoireStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"wishlist" => Ok(RepertoireStatus::Wishlist),
+            b"learning" => Ok(RepertoireStatus::Learning),
+            b"polishing" => Ok(RepertoireStatus::Polishing),
+            b"performance_ready" => Ok(RepertoireStatus::PerformanceReady),
+            b"retired" => Ok(RepertoireStatus::Retired),
+            other => Err(format!(
+                "Unknown repertoire status: {}",
+                String::from_utf8_lossy(other)
+            )
+            .into()),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, Identifiable)]
+#[diesel(primary_key(user_id, piece_id))]
+#[diesel(table_name = repertoire)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RepertoireEntry {
+    pub user_id: i32,
+    pub piece_id: i32,
+    pub status: RepertoireStatus,
+    pub target_date: Option<chrono::NaiveDate>,
+    pub notes: Option<String>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Display for RepertoireEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "USER: {} PIECE: {} STATUS: {}",
+            self.user_id, self.piece_id, self.status
+        )?;
+        if let Some(target_date) = self.target_date {
+            write!(f, " TARGET: {target_date}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = repertoire)]
+#[diesel(treat_none_as_null = true)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InsertableRepertoireEntry {
+    pub user_id: i32,
+    pub piece_id: i32,
+    pub status: RepertoireStatus,
+    pub target_date: Option<chrono::NaiveDate>,
+    pub notes: Option<String>,
+}
+
+// what a practice goal counts within each period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalMetric {
+    Minutes,  // minutes practiced, or minutes allocated to the goal's piece
+    Sessions, // practice sessions, or sessions including the goal's piece
+    Days,     // distinct days with practice
+}
+
+impl GoalMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GoalMetric::Minutes => "minutes",
+            GoalMetric::Sessions => "sessions",
+            GoalMetric::Days => "days",
+        }
+    }
+}
+
+impl Display for GoalMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl ToSql<Varchar, Pg> for GoalMetric {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for GoalMetric {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"minutes" => Ok(GoalMetric::Minutes),
+            b"sessions" => Ok(GoalMetric::Sessions),
+            b"days" => Ok(GoalMetric::Days),
+            other => Err(format!("Unknown goal metric: {}", String::from_utf8_lossy(other)).into()),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, Identifiable)]
+#[diesel(primary_key(goal_id))]
+#[diesel(table_name = practice_goals)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PracticeGoal {
+    pub goal_id: i32,
+    pub user_id: i32,
+    pub metric: GoalMetric,
+    pub period: Period,
+    pub target: i32,
+    pub piece_id: Option<i32>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub instrument_id: Option<i32>,
+}
+
+impl Display for PracticeGoal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "({}) USER: {} TARGET: {} {} PER {}",
+            self.goal_id,
+            self.user_id,
+            self.target,
+            self.metric,
+            self.period.as_str()
+        )?;
+        if let Some(piece_id) = self.piece_id {
+            write!(f, " PIECE: {piece_id}")?;
+        }
+        if let Some(instrument_id) = self.instrument_id {
+            write!(f, " INSTRUMENT: {instrument_id}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = practice_goals)]
+#[diesel(treat_none_as_null = true)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InsertablePracticeGoal {
+    pub user_id: i32,
+    pub metric: GoalMetric,
+    pub period: Period,
+    pub target: i32,
+    pub piece_id: Option<i32>,
+    pub instrument_id: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Identifiable)]
+#[diesel(primary_key(tag_id))]
+#[diesel(table_name = tags)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Tag {
+    pub tag_id: i32,
+    pub user_id: i32,
+    pub name: String,
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "({}) NAME: {} USER: {}",
+            self.tag_id, self.name, self.user_id
         )
     }
 }
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Insertable)]
+#[diesel(primary_key(practice_session_id, tag_id))]
+#[diesel(belongs_to(PracticeSession))]
+#[diesel(belongs_to(Tag))]
+#[diesel(table_name = practice_session_tags)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PracticeSessionTag {
+    pub practice_session_id: i32,
+    pub tag_id: i32,
+}
+
+#[derive(Queryable, Selectable, Serialize, Identifiable, Associations)]
+#[diesel(primary_key(tempo_entry_id))]
+#[diesel(belongs_to(PracticeSession))]
+#[diesel(table_name = tempo_entries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TempoEntry {
+    pub tempo_entry_id: i32,
+    pub practice_session_id: i32,
+    pub piece_id: i32,
+    pub passage: Option<String>, // null for the piece as a whole
+    pub target_bpm: Option<i32>,
+    pub achieved_bpm: i32,
+}
+
+impl Display for TempoEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "({}) SESSION: {} PIECE: {}",
+            self.tempo_entry_id, self.practice_session_id, self.piece_id
+        )?;
+        if let Some(passage) = &self.passage {
+            write!(f, " PASSAGE: {passage}")?;
+        }
+        write!(f, " BPM: {}", self.achieved_bpm)?;
+        if let Some(target_bpm) = self.target_bpm {
+            write!(f, "/{target_bpm}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = tempo_entries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InsertableTempoEntry {
+    pub practice_session_id: i32,
+    pub piece_id: i32,
+    pub passage: Option<String>,
+    pub target_bpm: Option<i32>,
+    pub achieved_bpm: i32,
+}
+
+#[derive(Queryable, Selectable, Serialize, Identifiable)]
+#[diesel(primary_key(user_id))]
+#[diesel(table_name = practice_timers)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PracticeTimer {
+    pub user_id: i32,
+    pub instrument_id: i32,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub paused_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub paused_secs: i32,
+}
+
+impl Display for PracticeTimer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "USER: {} INSTRUMENT: {} STARTED: {}",
+            self.user_id, self.instrument_id, self.started_at
+        )?;
+        if let Some(paused_at) = self.paused_at {
+            write!(f, " PAUSED: {paused_at}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, Identifiable, Associations)]
+#[diesel(primary_key(segment_id))]
+#[diesel(belongs_to(PracticeTimer, foreign_key = user_id))]
+#[diesel(table_name = practice_timer_segments)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PracticeTimerSegment {
+    pub segment_id: i32,
+    pub user_id: i32,
+    pub piece_id: Option<i32>,
+    pub start_secs: i32,
+}
+
+// the broad group an instrument belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum InstrumentFamily {
+    Strings,
+    Woodwinds,
+    Brass,
+    Percussion,
+    Keyboard,
+    Voice,
+    Other,
+}
+
+impl InstrumentFamily {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstrumentFamily::Strings => "strings",
+            InstrumentFamily::Woodwinds => "woodwinds",
+            InstrumentFamily::Brass => "brass",
+            InstrumentFamily::Percussion => "percussion",
+            InstrumentFamily::Keyboard => "keyboard",
+            InstrumentFamily::Voice => "voice",
+            InstrumentFamily::Other => "other",
+        }
+    }
+}
+
+impl Display for InstrumentFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl ToSql<Varchar, Pg> for InstrumentFamily {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for InstrumentFamily {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"strings" => Ok(InstrumentFamily::Strings),
+            b"woodwinds" => Ok(InstrumentFamily::Woodwinds),
+            b"brass" => Ok(InstrumentFamily::Brass),
+            b"percussion" => Ok(InstrumentFamily::Percussion),
+            b"keyboard" => Ok(InstrumentFamily::Keyboard),
+            b"voice" => Ok(InstrumentFamily::Voice),
+            b"other" => Ok(InstrumentFamily::Other),
+            other => Err(format!(
+                "Unknown instrument family: {}",
+                String::from_utf8_lossy(other)
+            )
+            .into()),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, Identifiable)]
+#[diesel(primary_key(instrument_id))]
+#[diesel(table_name = instruments)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Instrument {
+    pub instrument_id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub family: Option<InstrumentFamily>,
+    pub notes: Option<String>,
+}
+
+impl Display for Instrument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "({}) NAME: {} USER: {}",
+            self.instrument_id, self.name, self.user_id
+        )?;
+        if let Some(family) = self.family {
+            write!(f, " FAMILY: {family}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = instruments)]
+#[diesel(treat_none_as_null = true)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InsertableInstrument {
+    pub user_id: i32,
+    pub name: String,
+    pub family: Option<InstrumentFamily>,
+    pub notes: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(primary_key(session_id))]
+#[diesel(table_name = sessions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbSession {
+    pub session_id: String,
+    pub session_data: String,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
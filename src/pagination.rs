@@ -0,0 +1,90 @@
+use crate::AppError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+// position of the last row of a page: the value of the sort column plus the row's id to
+// break ties; only meaningful when reused with the same sort key and order
+#[derive(Serialize, Deserialize)]
+pub struct Cursor<T> {
+    pub value: T,
+    pub id: i32,
+}
+
+impl<T: Serialize> Cursor<T> {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("cursor should serialize"))
+    }
+}
+
+impl<T: DeserializeOwned> Cursor<T> {
+    pub fn decode(cursor: &str) -> Result<Self, AppError> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or(AppError::ClientError("Invalid cursor".to_owned()))
+    }
+}
+
+pub fn page_size(limit: Option<i64>) -> Result<i64, AppError> {
+    match limit {
+        None => Ok(DEFAULT_PAGE_SIZE),
+        Some(limit) if (1..=MAX_PAGE_SIZE).contains(&limit) => Ok(limit),
+        Some(_) => Err(AppError::ClientError(format!(
+            "limit must be between 1 and {MAX_PAGE_SIZE}"
+        ))),
+    }
+}
+
+pub fn offset(offset: Option<i64>) -> Result<i64, AppError> {
+    match offset {
+        None => Ok(0),
+        Some(offset) if offset >= 0 => Ok(offset),
+        Some(_) => Err(AppError::ClientError(
+            "offset must not be negative".to_owned(),
+        )),
+    }
+}
+
+// orders a boxed query by the sort column then id, and if a cursor is given only keeps
+// the rows that come after it in that order
+#[macro_export]
+macro_rules! order_after_cursor {
+    ($query:ident, $sort_column:expr, $id_column:expr, $sort_order:expr, $cursor:expr) => {{
+        let cursor = $cursor;
+        match $sort_order {
+            $crate::pagination::SortOrder::Asc => {
+                if let Some(cursor) = cursor {
+                    $query = $query.filter(
+                        $sort_column
+                            .gt(cursor.value.clone())
+                            .or($sort_column.eq(cursor.value).and($id_column.gt(cursor.id))),
+                    );
+                }
+                $query.order(($sort_column.asc(), $id_column.asc()))
+            }
+            $crate::pagination::SortOrder::Desc => {
+                if let Some(cursor) = cursor {
+                    $query = $query.filter(
+                        $sort_column
+                            .lt(cursor.value.clone())
+                            .or($sort_column.eq(cursor.value).and($id_column.lt(cursor.id))),
+                    );
+                }
+                $query.order(($sort_column.desc(), $id_column.desc()))
+            }
+        }
+    }};
+}
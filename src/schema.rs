@@ -1,10 +1,40 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    composers (composer_id) {
+        composer_id -> Int4,
+        name -> Varchar,
+        sort_name -> Varchar,
+        birth_year -> Nullable<Int4>,
+        death_year -> Nullable<Int4>,
+        era -> Nullable<Varchar>,
+        open_opus_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    instruments (instrument_id) {
+        instrument_id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        family -> Nullable<Varchar>,
+        notes -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     pieces (piece_id) {
         piece_id -> Int4,
         title -> Varchar,
-        composer -> Varchar,
+        created_by -> Nullable<Int4>,
+        composer_id -> Int4,
+        catalogue_number -> Nullable<Varchar>,
+        key -> Nullable<Varchar>,
+        instrumentation -> Nullable<Varchar>,
+        genre -> Nullable<Varchar>,
+        parent_piece_id -> Nullable<Int4>,
+        movement_number -> Nullable<Int4>,
+        open_opus_id -> Nullable<Int4>,
     }
 }
 
@@ -12,16 +42,98 @@ diesel::table! {
     pieces_practiced (practice_session_id, piece_id) {
         practice_session_id -> Int4,
         piece_id -> Int4,
+        duration_mins -> Nullable<Int4>,
+        start_offset_mins -> Nullable<Int4>,
+        notes -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    practice_goals (goal_id) {
+        goal_id -> Int4,
+        user_id -> Int4,
+        metric -> Varchar,
+        period -> Varchar,
+        target -> Int4,
+        piece_id -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        instrument_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    practice_session_tags (practice_session_id, tag_id) {
+        practice_session_id -> Int4,
+        tag_id -> Int4,
     }
 }
 
 diesel::table! {
     practice_sessions (practice_session_id) {
         practice_session_id -> Int4,
-        start_datetime -> Timestamp,
+        start_datetime -> Timestamptz,
         duration_mins -> Int4,
-        instrument -> Varchar,
         user_id -> Int4,
+        notes -> Nullable<Text>,
+        rating -> Nullable<Int4>,
+        instrument_id -> Int4,
+    }
+}
+
+diesel::table! {
+    practice_timer_segments (segment_id) {
+        segment_id -> Int4,
+        user_id -> Int4,
+        piece_id -> Nullable<Int4>,
+        start_secs -> Int4,
+    }
+}
+
+diesel::table! {
+    practice_timers (user_id) {
+        user_id -> Int4,
+        instrument_id -> Int4,
+        started_at -> Timestamptz,
+        paused_at -> Nullable<Timestamptz>,
+        paused_secs -> Int4,
+    }
+}
+
+diesel::table! {
+    repertoire (user_id, piece_id) {
+        user_id -> Int4,
+        piece_id -> Int4,
+        status -> Varchar,
+        target_date -> Nullable<Date>,
+        notes -> Nullable<Text>,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    sessions (session_id) {
+        session_id -> Varchar,
+        session_data -> Text,
+        expires_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    tags (tag_id) {
+        tag_id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+    }
+}
+
+diesel::table! {
+    tempo_entries (tempo_entry_id) {
+        tempo_entry_id -> Int4,
+        practice_session_id -> Int4,
+        piece_id -> Int4,
+        passage -> Nullable<Varchar>,
+        target_bpm -> Nullable<Int4>,
+        achieved_bpm -> Int4,
     }
 }
 
@@ -30,16 +142,48 @@ diesel::table! {
         user_id -> Int4,
         user_name -> Varchar,
         password_hash -> Varchar,
+        timezone -> Varchar,
+        calendar_token -> Nullable<Varchar>,
+        role -> Varchar,
+        disabled_at -> Nullable<Timestamptz>,
     }
 }
 
+diesel::joinable!(instruments -> users (user_id));
+diesel::joinable!(pieces -> composers (composer_id));
+diesel::joinable!(pieces -> users (created_by));
 diesel::joinable!(pieces_practiced -> pieces (piece_id));
 diesel::joinable!(pieces_practiced -> practice_sessions (practice_session_id));
+diesel::joinable!(practice_goals -> instruments (instrument_id));
+diesel::joinable!(practice_goals -> pieces (piece_id));
+diesel::joinable!(practice_goals -> users (user_id));
+diesel::joinable!(practice_session_tags -> practice_sessions (practice_session_id));
+diesel::joinable!(practice_session_tags -> tags (tag_id));
+diesel::joinable!(practice_sessions -> instruments (instrument_id));
 diesel::joinable!(practice_sessions -> users (user_id));
+diesel::joinable!(practice_timer_segments -> pieces (piece_id));
+diesel::joinable!(practice_timer_segments -> practice_timers (user_id));
+diesel::joinable!(practice_timers -> instruments (instrument_id));
+diesel::joinable!(practice_timers -> users (user_id));
+diesel::joinable!(repertoire -> pieces (piece_id));
+diesel::joinable!(repertoire -> users (user_id));
+diesel::joinable!(tags -> users (user_id));
+diesel::joinable!(tempo_entries -> pieces (piece_id));
+diesel::joinable!(tempo_entries -> practice_sessions (practice_session_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    composers,
+    instruments,
     pieces,
     pieces_practiced,
+    practice_goals,
+    practice_session_tags,
     practice_sessions,
+    practice_timer_segments,
+    practice_timers,
+    repertoire,
+    sessions,
+    tags,
+    tempo_entries,
     users,
 );
@@ -0,0 +1,98 @@
+use crate::models::Piece;
+use crate::schema::pieces;
+use crate::{map_backend_err, pieces_with_composers, AppError};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Float4, Integer, Text};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(QueryableByName)]
+struct PieceScore {
+    #[diesel(sql_type = Integer)]
+    piece_id: i32,
+    #[diesel(sql_type = Float4)]
+    score: f32,
+}
+
+#[derive(Serialize)]
+pub struct PieceMatch {
+    #[serde(flatten)]
+    pub piece: Piece,
+    pub score: f32,
+}
+
+// pieces ranked by how well they match the search, in any word order and ignoring accents and
+// punctuation. Candidates are found through the full-text indexes (which also match other
+// forms of a word, "variation" for "Variations") and the trigram indexes (which allow for typos,
+// "beethovn"); the score averages how closely each word of the search matches the title,
+// composer or catalogue number, plus the full-text rank when every word matches
+pub fn search_pieces(
+    conn: &mut PgConnection,
+    search: &str,
+    limit: i64,
+) -> Result<Vec<PieceMatch>, AppError> {
+    if search.chars().all(|c| !c.is_alphanumeric()) {
+        return Err(AppError::ClientError("Missing search query".to_owned()));
+    }
+
+    let scores: Vec<PieceScore> = map_backend_err!(diesel::sql_query(
+        "WITH query AS ( \
+             SELECT words, \
+                    to_tsquery('english', array_to_string(words, ' | ')) AS any_word, \
+                    to_tsquery('english', array_to_string(words, ' & ')) AS all_words \
+             FROM (SELECT string_to_array(normalize_text($1), ' ') AS words) split \
+         ), \
+         candidates AS ( \
+             SELECT p.piece_id FROM pieces p, query \
+             WHERE to_tsvector('english', normalize_text(p.title || ' ' || coalesce(p.catalogue_number, ''))) \
+                   @@ query.any_word \
+             UNION \
+             SELECT p.piece_id FROM pieces p JOIN composers c ON c.composer_id = p.composer_id, query \
+             WHERE to_tsvector('english', normalize_text(c.name)) @@ query.any_word \
+             UNION \
+             SELECT p.piece_id FROM pieces p, query, unnest(query.words) word \
+             WHERE word <% normalize_text(p.title) \
+             UNION \
+             SELECT p.piece_id FROM pieces p JOIN composers c ON c.composer_id = p.composer_id, \
+                    query, unnest(query.words) word \
+             WHERE word <% normalize_text(c.name) \
+         ) \
+         SELECT p.piece_id, \
+                ((SELECT avg(greatest( \
+                      word_similarity(word, normalize_text(p.title)), \
+                      word_similarity(word, normalize_text(c.name)), \
+                      word_similarity(word, coalesce(normalize_text(p.catalogue_number), '')) \
+                  )) FROM unnest(query.words) word) \
+                 + ts_rank( \
+                      to_tsvector('english', normalize_text(p.title || ' ' || coalesce(p.catalogue_number, ''))) \
+                      || to_tsvector('english', normalize_text(c.name)), \
+                      query.all_words \
+                 ))::REAL AS score \
+         FROM candidates \
+         JOIN pieces p ON p.piece_id = candidates.piece_id \
+         JOIN composers c ON c.composer_id = p.composer_id, \
+              query \
+         ORDER BY score DESC, p.piece_id \
+         LIMIT $2",
+    )
+    .bind::<Text, _>(search)
+    .bind::<BigInt, _>(limit)
+    .load::<PieceScore>(conn))?;
+
+    let mut pieces: HashMap<i32, Piece> = map_backend_err!(pieces_with_composers()
+        .filter(pieces::piece_id.eq_any(scores.iter().map(|score| score.piece_id)))
+        .load::<Piece>(conn))?
+    .into_iter()
+    .map(|piece| (piece.piece_id, piece))
+    .collect();
+
+    Ok(scores
+        .into_iter()
+        .filter_map(|score| {
+            Some(PieceMatch {
+                piece: pieces.remove(&score.piece_id)?,
+                score: score.score,
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,126 @@
+use crate::models::DbSession;
+use crate::schema::sessions;
+use axum_sessions::async_session::{async_trait, serde_json, Result, Session, SessionStore};
+use chrono::Utc;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::{Bool, Text};
+use diesel::upsert::excluded;
+use log::{error, info};
+use std::time::Duration;
+
+// session store that keeps sessions in the sessions table, so that logins survive
+// restarts and are shared between every instance of the server
+#[derive(Clone, Debug)]
+pub struct PgSessionStore {
+    db: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl PgSessionStore {
+    pub fn new(db: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { db }
+    }
+
+    // deletes every session that has expired, returning the number deleted
+    pub fn cleanup(&self) -> Result<usize> {
+        let mut conn = self.db.get()?;
+
+        let rows_deleted =
+            diesel::delete(sessions::table.filter(sessions::expires_at.lt(Utc::now())))
+                .execute(&mut conn)?;
+
+        Ok(rows_deleted)
+    }
+
+    // runs cleanup on the given interval for as long as the server is up
+    pub fn spawn_cleanup_task(&self, period: Duration) {
+        let store = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                match store.cleanup() {
+                    Ok(rows_deleted) => info!("Cleaned up {rows_deleted} expired sessions"),
+                    Err(e) => error!("Failed to clean up expired sessions: {e}"),
+                }
+            }
+        });
+    }
+}
+
+// logs a user out everywhere, e.g. when they're disabled or deleted; session data is json,
+// with the user id stored as a json encoded string under "data"
+pub fn delete_user_sessions(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::delete(
+        sessions::table.filter(
+            sql::<Bool>("(session_data::jsonb -> 'data' ->> 'user_id') = ")
+                .bind::<Text, _>(user_id.to_string()),
+        ),
+    )
+    .execute(conn)
+}
+
+#[async_trait]
+impl SessionStore for PgSessionStore {
+    async fn load_session(&self, cookie_value: String) -> Result<Option<Session>> {
+        let session_id = Session::id_from_cookie_value(&cookie_value)?;
+
+        let mut conn = self.db.get()?;
+
+        let session_data: Option<String> = sessions::table
+            .select(sessions::session_data)
+            .filter(sessions::session_id.eq(session_id))
+            .first::<String>(&mut conn)
+            .optional()?;
+
+        match session_data {
+            Some(session_data) => {
+                let session: Session = serde_json::from_str(&session_data)?;
+                Ok(session.validate())
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn store_session(&self, session: Session) -> Result<Option<String>> {
+        let db_session = DbSession {
+            session_id: session.id().to_owned(),
+            session_data: serde_json::to_string(&session)?,
+            expires_at: session.expiry().copied(),
+        };
+
+        let mut conn = self.db.get()?;
+
+        diesel::insert_into(sessions::table)
+            .values(&db_session)
+            .on_conflict(sessions::session_id)
+            .do_update()
+            .set((
+                sessions::session_data.eq(excluded(sessions::session_data)),
+                sessions::expires_at.eq(excluded(sessions::expires_at)),
+            ))
+            .execute(&mut conn)?;
+
+        session.reset_data_changed();
+        Ok(session.into_cookie_value())
+    }
+
+    async fn destroy_session(&self, session: Session) -> Result {
+        let mut conn = self.db.get()?;
+
+        diesel::delete(sessions::table.filter(sessions::session_id.eq(session.id())))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    async fn clear_store(&self) -> Result {
+        let mut conn = self.db.get()?;
+
+        diesel::delete(sessions::table).execute(&mut conn)?;
+
+        Ok(())
+    }
+}
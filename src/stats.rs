@@ -0,0 +1,409 @@
+use crate::instruments::lower;
+use crate::schema::{
+    composers, instruments, pieces, pieces_practiced, practice_session_tags, practice_sessions,
+    tags, tempo_entries,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::dsl::{self, count_distinct, count_star};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::{BigInt, Integer, Nullable, Text, Timestamp, Timestamptz, Varchar};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+// also stored as the period of practice goals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    // the field name postgres' date_trunc expects
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for Period {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for Period {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"day" => Ok(Period::Day),
+            b"week" => Ok(Period::Week),
+            b"month" => Ok(Period::Month),
+            other => Err(format!("Unknown period: {}", String::from_utf8_lossy(other)).into()),
+        }
+    }
+}
+
+// inclusive bounds on the start time of the practice sessions included in the stats
+pub struct DateRange {
+    pub min_datetime: Option<DateTime<Utc>>,
+    pub max_datetime: Option<DateTime<Utc>>,
+}
+
+// period_start is a wall clock time in the user's timezone
+#[derive(QueryableByName, Serialize)]
+pub struct PeriodTotal {
+    #[diesel(sql_type = Timestamp)]
+    pub period_start: NaiveDateTime,
+    #[diesel(sql_type = BigInt)]
+    pub total_mins: i64,
+    #[diesel(sql_type = BigInt)]
+    pub num_sessions: i64,
+}
+
+#[derive(Serialize)]
+pub struct InstrumentTotal {
+    pub instrument_id: i32,
+    pub instrument: String,
+    pub total_mins: i64,
+    pub num_sessions: i64,
+}
+
+// total_mins only counts time explicitly allocated to the piece within each session
+#[derive(Serialize)]
+pub struct PieceTotal {
+    pub piece_id: i32,
+    pub title: String,
+    pub composer: String,
+    pub total_mins: i64,
+    pub num_sessions: i64,
+}
+
+#[derive(Serialize)]
+pub struct ComposerTotal {
+    pub composer_id: i32,
+    pub composer: String,
+    pub total_mins: i64,
+    pub num_sessions: i64,
+}
+
+// sessions count towards each of their tags, so totals across tags can exceed the overall total
+#[derive(Serialize)]
+pub struct TagTotal {
+    pub tag_id: i32,
+    pub tag: String,
+    pub total_mins: i64,
+    pub num_sessions: i64,
+}
+
+#[derive(Serialize)]
+pub struct TempoPoint {
+    pub tempo_entry_id: i32,
+    pub practice_session_id: i32,
+    pub start_datetime: DateTime<Utc>,
+    pub target_bpm: Option<i32>,
+    pub achieved_bpm: i32,
+}
+
+// the tempos reached on one passage of a piece (or the piece as a whole), oldest first
+#[derive(Serialize)]
+pub struct TempoSeries {
+    pub passage: Option<String>,
+    pub points: Vec<TempoPoint>,
+}
+
+pub fn minutes_by_period(
+    conn: &mut PgConnection,
+    user_id: i32,
+    period: Period,
+    timezone: &Tz,
+    date_range: &DateRange,
+) -> QueryResult<Vec<PeriodTotal>> {
+    // grouping by an expression isn't expressible in the query builder, so this one is raw sql;
+    // sessions are bucketed by the day/week/month they fall in within the user's timezone
+    diesel::sql_query(
+        "SELECT date_trunc($1, start_datetime AT TIME ZONE $2) AS period_start, \
+                SUM(duration_mins)::BIGINT AS total_mins, \
+                COUNT(*) AS num_sessions \
+         FROM practice_sessions \
+         WHERE user_id = $3 \
+           AND ($4 IS NULL OR start_datetime >= $4) \
+           AND ($5 IS NULL OR start_datetime <= $5) \
+         GROUP BY period_start \
+         ORDER BY period_start",
+    )
+    .bind::<Text, _>(period.as_str())
+    .bind::<Text, _>(timezone.name())
+    .bind::<Integer, _>(user_id)
+    .bind::<Nullable<Timestamptz>, _>(date_range.min_datetime)
+    .bind::<Nullable<Timestamptz>, _>(date_range.max_datetime)
+    .load(conn)
+}
+
+pub fn minutes_by_instrument(
+    conn: &mut PgConnection,
+    user_id: i32,
+    date_range: &DateRange,
+) -> QueryResult<Vec<InstrumentTotal>> {
+    let mut query = practice_sessions::table
+        .inner_join(instruments::table)
+        .filter(practice_sessions::user_id.eq(user_id))
+        .group_by(instruments::instrument_id)
+        .select((
+            instruments::instrument_id,
+            instruments::name,
+            dsl::sum(practice_sessions::duration_mins),
+            count_star(),
+        ))
+        .into_boxed();
+
+    if let Some(min_datetime) = date_range.min_datetime {
+        query = query.filter(practice_sessions::start_datetime.ge(min_datetime));
+    }
+
+    if let Some(max_datetime) = date_range.max_datetime {
+        query = query.filter(practice_sessions::start_datetime.le(max_datetime));
+    }
+
+    let totals = query
+        .order(dsl::sum(practice_sessions::duration_mins).desc())
+        .load::<(i32, String, Option<i64>, i64)>(conn)?
+        .into_iter()
+        .map(
+            |(instrument_id, instrument, total_mins, num_sessions)| InstrumentTotal {
+                instrument_id,
+                instrument,
+                total_mins: total_mins.unwrap_or(0),
+                num_sessions,
+            },
+        )
+        .collect();
+
+    Ok(totals)
+}
+
+// lets pieces be grouped by id along with their composer, whose name is selected with them
+diesel::allow_columns_to_appear_in_same_group_by_clause!(
+    pieces::piece_id,
+    pieces::title,
+    composers::composer_id,
+    composers::name,
+);
+
+pub fn minutes_by_piece(
+    conn: &mut PgConnection,
+    user_id: i32,
+    date_range: &DateRange,
+) -> QueryResult<Vec<PieceTotal>> {
+    let mut query = pieces_practiced::table
+        .inner_join(practice_sessions::table)
+        .inner_join(pieces::table.inner_join(composers::table))
+        .filter(practice_sessions::user_id.eq(user_id))
+        .group_by((pieces::piece_id, composers::composer_id))
+        .select((
+            pieces::piece_id,
+            pieces::title,
+            composers::name,
+            dsl::sum(pieces_practiced::duration_mins),
+            count_distinct(practice_sessions::practice_session_id),
+        ))
+        .into_boxed();
+
+    if let Some(min_datetime) = date_range.min_datetime {
+        query = query.filter(practice_sessions::start_datetime.ge(min_datetime));
+    }
+
+    if let Some(max_datetime) = date_range.max_datetime {
+        query = query.filter(practice_sessions::start_datetime.le(max_datetime));
+    }
+
+    let totals = query
+        .order(
+            dsl::sum(pieces_practiced::duration_mins)
+                .desc()
+                .nulls_last(),
+        )
+        .load::<(i32, String, String, Option<i64>, i64)>(conn)?
+        .into_iter()
+        .map(
+            |(piece_id, title, composer, total_mins, num_sessions)| PieceTotal {
+                piece_id,
+                title,
+                composer,
+                total_mins: total_mins.unwrap_or(0),
+                num_sessions,
+            },
+        )
+        .collect();
+
+    Ok(totals)
+}
+
+pub fn minutes_by_composer(
+    conn: &mut PgConnection,
+    user_id: i32,
+    date_range: &DateRange,
+) -> QueryResult<Vec<ComposerTotal>> {
+    let mut query = pieces_practiced::table
+        .inner_join(practice_sessions::table)
+        .inner_join(pieces::table.inner_join(composers::table))
+        .filter(practice_sessions::user_id.eq(user_id))
+        .group_by(composers::composer_id)
+        .select((
+            composers::composer_id,
+            composers::name,
+            dsl::sum(pieces_practiced::duration_mins),
+            count_distinct(practice_sessions::practice_session_id),
+        ))
+        .into_boxed();
+
+    if let Some(min_datetime) = date_range.min_datetime {
+        query = query.filter(practice_sessions::start_datetime.ge(min_datetime));
+    }
+
+    if let Some(max_datetime) = date_range.max_datetime {
+        query = query.filter(practice_sessions::start_datetime.le(max_datetime));
+    }
+
+    let totals = query
+        .order(
+            dsl::sum(pieces_practiced::duration_mins)
+                .desc()
+                .nulls_last(),
+        )
+        .load::<(i32, String, Option<i64>, i64)>(conn)?
+        .into_iter()
+        .map(
+            |(composer_id, composer, total_mins, num_sessions)| ComposerTotal {
+                composer_id,
+                composer,
+                total_mins: total_mins.unwrap_or(0),
+                num_sessions,
+            },
+        )
+        .collect();
+
+    Ok(totals)
+}
+
+pub fn minutes_by_tag(
+    conn: &mut PgConnection,
+    user_id: i32,
+    date_range: &DateRange,
+) -> QueryResult<Vec<TagTotal>> {
+    let mut query = practice_session_tags::table
+        .inner_join(practice_sessions::table)
+        .inner_join(tags::table)
+        .filter(practice_sessions::user_id.eq(user_id))
+        .group_by(tags::tag_id)
+        .select((
+            tags::tag_id,
+            tags::name,
+            dsl::sum(practice_sessions::duration_mins),
+            count_star(),
+        ))
+        .into_boxed();
+
+    if let Some(min_datetime) = date_range.min_datetime {
+        query = query.filter(practice_sessions::start_datetime.ge(min_datetime));
+    }
+
+    if let Some(max_datetime) = date_range.max_datetime {
+        query = query.filter(practice_sessions::start_datetime.le(max_datetime));
+    }
+
+    let totals = query
+        .order((
+            dsl::sum(practice_sessions::duration_mins).desc(),
+            tags::name,
+        ))
+        .load::<(i32, String, Option<i64>, i64)>(conn)?
+        .into_iter()
+        .map(|(tag_id, tag, total_mins, num_sessions)| TagTotal {
+            tag_id,
+            tag,
+            total_mins: total_mins.unwrap_or(0),
+            num_sessions,
+        })
+        .collect();
+
+    Ok(totals)
+}
+
+// one series per passage of the piece the user has logged tempos for, with the piece as a whole
+// first; passages are told apart ignoring case. When a passage is given only its series is found
+pub fn tempo_series(
+    conn: &mut PgConnection,
+    user_id: i32,
+    piece_id: i32,
+    passage: Option<&str>,
+    date_range: &DateRange,
+) -> QueryResult<Vec<TempoSeries>> {
+    let mut query = tempo_entries::table
+        .inner_join(practice_sessions::table)
+        .filter(practice_sessions::user_id.eq(user_id))
+        .filter(tempo_entries::piece_id.eq(piece_id))
+        .select((
+            tempo_entries::tempo_entry_id,
+            tempo_entries::practice_session_id,
+            practice_sessions::start_datetime,
+            tempo_entries::passage,
+            tempo_entries::target_bpm,
+            tempo_entries::achieved_bpm,
+        ))
+        .into_boxed();
+
+    if let Some(passage) = passage {
+        query = query.filter(lower(tempo_entries::passage.assume_not_null()).eq(lower(
+            passage.split_whitespace().collect::<Vec<&str>>().join(" "),
+        )));
+    }
+
+    if let Some(min_datetime) = date_range.min_datetime {
+        query = query.filter(practice_sessions::start_datetime.ge(min_datetime));
+    }
+
+    if let Some(max_datetime) = date_range.max_datetime {
+        query = query.filter(practice_sessions::start_datetime.le(max_datetime));
+    }
+
+    let rows = query
+        .order((
+            practice_sessions::start_datetime,
+            tempo_entries::tempo_entry_id,
+        ))
+        .load::<(i32, i32, DateTime<Utc>, Option<String>, Option<i32>, i32)>(conn)?;
+
+    let mut series: BTreeMap<Option<String>, TempoSeries> = BTreeMap::new();
+    for (tempo_entry_id, practice_session_id, start_datetime, passage, target_bpm, achieved_bpm) in
+        rows
+    {
+        series
+            .entry(passage.as_deref().map(str::to_lowercase))
+            .or_insert_with(|| TempoSeries {
+                passage,
+                points: Vec::new(),
+            })
+            .points
+            .push(TempoPoint {
+                tempo_entry_id,
+                practice_session_id,
+                start_datetime,
+                target_bpm,
+                achieved_bpm,
+            });
+    }
+
+    Ok(series.into_values().collect())
+}
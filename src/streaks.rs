@@ -0,0 +1,204 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct WeekdayAverage {
+    pub weekday: Weekday,
+    pub average_mins: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PracticeStreaks {
+    pub current_streak_days: u32,
+    pub longest_streak_days: u32,
+    pub window_days: u32,
+    pub days_practiced_in_window: u32,
+    pub weekday_averages: Vec<WeekdayAverage>,
+}
+
+// computes streaks from (start_datetime, duration_mins) pairs; sessions after `today` are
+// ignored, and the current streak is still alive if the last practice was yesterday
+pub fn calculate_streaks(
+    practice_sessions: &[(NaiveDateTime, i32)],
+    today: NaiveDate,
+    window_days: u32,
+) -> PracticeStreaks {
+    let mut mins_per_day: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    for (start_datetime, duration_mins) in practice_sessions {
+        let date = start_datetime.date();
+        if date <= today {
+            *mins_per_day.entry(date).or_insert(0) += i64::from(*duration_mins);
+        }
+    }
+
+    let mut longest_streak_days = 0;
+    let mut streak_days = 0;
+    let mut previous_date: Option<NaiveDate> = None;
+    for &date in mins_per_day.keys() {
+        streak_days = match previous_date {
+            Some(previous_date) if date - previous_date == Duration::days(1) => streak_days + 1,
+            _ => 1,
+        };
+        longest_streak_days = longest_streak_days.max(streak_days);
+        previous_date = Some(date);
+    }
+
+    let mut current_streak_days = 0;
+    let mut date = if mins_per_day.contains_key(&today) {
+        today
+    } else {
+        today - Duration::days(1)
+    };
+    while mins_per_day.contains_key(&date) {
+        current_streak_days += 1;
+        date -= Duration::days(1);
+    }
+
+    let mut days_practiced_in_window = 0;
+    let mut weekday_totals = [(0i64, 0u32); 7]; // (minutes, occurrences of the weekday)
+    for days_ago in 0..window_days {
+        let date = today - Duration::days(i64::from(days_ago));
+        let weekday_total = &mut weekday_totals[date.weekday().num_days_from_monday() as usize];
+        weekday_total.1 += 1;
+        if let Some(mins) = mins_per_day.get(&date) {
+            days_practiced_in_window += 1;
+            weekday_total.0 += mins;
+        }
+    }
+
+    let weekday_averages = WEEKDAYS
+        .iter()
+        .zip(weekday_totals)
+        .map(|(&weekday, (total_mins, occurrences))| WeekdayAverage {
+            weekday,
+            average_mins: if occurrences == 0 {
+                0.0
+            } else {
+                total_mins as f64 / f64::from(occurrences)
+            },
+        })
+        .collect();
+
+    PracticeStreaks {
+        current_streak_days,
+        longest_streak_days,
+        window_days,
+        days_practiced_in_window,
+        weekday_averages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(date: &str, duration_mins: i32) -> (NaiveDateTime, i32) {
+        (
+            NaiveDateTime::parse_from_str(&format!("{date}T18:00:00"), "%Y-%m-%dT%H:%M:%S")
+                .unwrap(),
+            duration_mins,
+        )
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn no_sessions() {
+        let streaks = calculate_streaks(&[], date("2024-03-10"), 7);
+
+        assert_eq!(streaks.current_streak_days, 0);
+        assert_eq!(streaks.longest_streak_days, 0);
+        assert_eq!(streaks.days_practiced_in_window, 0);
+        assert!(streaks
+            .weekday_averages
+            .iter()
+            .all(|average| average.average_mins == 0.0));
+    }
+
+    #[test]
+    fn current_and_longest_streaks() {
+        let sessions = [
+            session("2024-03-01", 30),
+            session("2024-03-02", 30),
+            session("2024-03-03", 30),
+            session("2024-03-04", 30),
+            // gap on the 5th
+            session("2024-03-06", 20),
+            session("2024-03-07", 20),
+            session("2024-03-07", 10), // second session on the same day
+            session("2024-03-08", 20),
+        ];
+
+        let streaks = calculate_streaks(&sessions, date("2024-03-08"), 7);
+
+        assert_eq!(streaks.current_streak_days, 3);
+        assert_eq!(streaks.longest_streak_days, 4);
+        assert_eq!(streaks.days_practiced_in_window, 6);
+    }
+
+    #[test]
+    fn current_streak_survives_until_end_of_today() {
+        let sessions = [session("2024-03-06", 20), session("2024-03-07", 20)];
+
+        assert_eq!(
+            calculate_streaks(&sessions, date("2024-03-08"), 7).current_streak_days,
+            2
+        );
+        assert_eq!(
+            calculate_streaks(&sessions, date("2024-03-09"), 7).current_streak_days,
+            0
+        );
+    }
+
+    #[test]
+    fn future_sessions_are_ignored() {
+        let sessions = [session("2024-03-08", 20), session("2024-03-09", 20)];
+
+        let streaks = calculate_streaks(&sessions, date("2024-03-08"), 7);
+
+        assert_eq!(streaks.current_streak_days, 1);
+        assert_eq!(streaks.longest_streak_days, 1);
+        assert_eq!(streaks.days_practiced_in_window, 1);
+    }
+
+    #[test]
+    fn weekday_averages_over_window() {
+        // 2024-03-04 and 2024-03-11 are both mondays
+        let sessions = [
+            session("2024-03-04", 60),
+            session("2024-03-11", 30),
+            session("2024-03-12", 45),
+        ];
+
+        let streaks = calculate_streaks(&sessions, date("2024-03-17"), 14);
+
+        assert_eq!(
+            streaks.weekday_averages[0],
+            WeekdayAverage {
+                weekday: Weekday::Mon,
+                average_mins: 45.0
+            }
+        );
+        assert_eq!(
+            streaks.weekday_averages[1],
+            WeekdayAverage {
+                weekday: Weekday::Tue,
+                average_mins: 22.5
+            }
+        );
+        assert_eq!(streaks.weekday_averages[2].average_mins, 0.0);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::models::{PracticeSessionTag, Tag};
+use crate::schema::{practice_session_tags, tags};
+use crate::AppError;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const MAX_TAG_NAME_LENGTH: usize = 50;
+
+// whether sessions filtered by several tags need any one of them or all of them
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+#[derive(Serialize)]
+pub struct TagWithSessionCount {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub num_sessions: i64,
+}
+
+// tags are trimmed, lowercased and have runs of whitespace collapsed, so " Sight  Reading"
+// and "sight reading" are the same tag
+pub fn normalize_tag_name(name: &str) -> Result<String, AppError> {
+    let name = name
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase();
+
+    if name.is_empty() {
+        return Err(AppError::ClientError("Missing tag name".to_owned()));
+    }
+    if name.chars().count() > MAX_TAG_NAME_LENGTH {
+        return Err(AppError::ClientError(format!(
+            "Tag names can be at most {MAX_TAG_NAME_LENGTH} characters"
+        )));
+    }
+
+    Ok(name)
+}
+
+// the user's tags with these names, creating any they don't have yet
+pub fn get_or_create_tags(
+    conn: &mut PgConnection,
+    user_id: i32,
+    names: &[String],
+) -> Result<Vec<Tag>, AppError> {
+    let mut names = names
+        .iter()
+        .map(|name| normalize_tag_name(name))
+        .collect::<Result<Vec<String>, AppError>>()?;
+    names.sort();
+    names.dedup();
+
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    diesel::insert_into(tags::table)
+        .values(
+            names
+                .iter()
+                .map(|name| (tags::user_id.eq(user_id), tags::name.eq(name)))
+                .collect::<Vec<_>>(),
+        )
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    Ok(tags::table
+        .filter(tags::user_id.eq(user_id))
+        .filter(tags::name.eq_any(&names))
+        .order(tags::name)
+        .load::<Tag>(conn)?)
+}
+
+// replaces the tags on a practice session with the given ones, by name
+pub fn set_practice_session_tags(
+    conn: &mut PgConnection,
+    user_id: i32,
+    practice_session_id: i32,
+    names: &[String],
+) -> Result<Vec<Tag>, AppError> {
+    diesel::delete(
+        practice_session_tags::table
+            .filter(practice_session_tags::practice_session_id.eq(practice_session_id)),
+    )
+    .execute(conn)?;
+
+    let tags = get_or_create_tags(conn, user_id, names)?;
+
+    diesel::insert_into(practice_session_tags::table)
+        .values(
+            tags.iter()
+                .map(|tag| PracticeSessionTag {
+                    practice_session_id,
+                    tag_id: tag.tag_id,
+                })
+                .collect::<Vec<PracticeSessionTag>>(),
+        )
+        .execute(conn)?;
+
+    Ok(tags)
+}
+
+// the tags on a practice session, alphabetically
+pub fn load_practice_session_tags(
+    conn: &mut PgConnection,
+    practice_session_id: i32,
+) -> QueryResult<Vec<Tag>> {
+    tags::table
+        .inner_join(practice_session_tags::table)
+        .filter(practice_session_tags::practice_session_id.eq(practice_session_id))
+        .select(Tag::as_select())
+        .order(tags::name)
+        .load::<Tag>(conn)
+}
+
+// all of the user's tags, including ones no session uses anymore, alphabetically
+pub fn tags_with_session_counts(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> QueryResult<Vec<TagWithSessionCount>> {
+    Ok(tags::table
+        .left_join(practice_session_tags::table)
+        .filter(tags::user_id.eq(user_id))
+        .group_by(tags::tag_id)
+        .select((
+            Tag::as_select(),
+            diesel::dsl::count(practice_session_tags::practice_session_id.nullable()),
+        ))
+        .order(tags::name)
+        .load::<(Tag, i64)>(conn)?
+        .into_iter()
+        .map(|(tag, num_sessions)| TagWithSessionCount { tag, num_sessions })
+        .collect())
+}
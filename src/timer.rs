@@ -0,0 +1,231 @@
+use crate::models::{Instrument, PracticeTimer, PracticeTimerSegment};
+use crate::schema::{practice_timer_segments, practice_timers};
+use crate::{AppError, NewPiecePracticedData};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error, Error::DatabaseError};
+use serde::Serialize;
+
+// a running or paused timer as the frontend needs it to pick up again after a refresh
+#[derive(Serialize)]
+pub struct TimerState {
+    #[serde(flatten)]
+    pub timer: PracticeTimer,
+    pub instrument: Instrument,
+    pub elapsed_secs: i32,
+    pub paused: bool,
+    pub piece_id: Option<i32>, // the piece currently being practiced
+    pub segments: Vec<PracticeTimerSegment>,
+}
+
+// seconds the timer has been running for, leaving out time spent paused
+pub fn elapsed_secs(timer: &PracticeTimer, now: DateTime<Utc>) -> i32 {
+    let until = timer.paused_at.unwrap_or(now);
+    let secs = (until - timer.started_at).num_seconds() - i64::from(timer.paused_secs);
+
+    i32::try_from(secs.max(0)).unwrap_or(i32::MAX)
+}
+
+// the user's timer, locked until the end of the transaction so concurrent requests (e.g. from
+// two open tabs) apply one after the other
+pub fn load_timer(conn: &mut PgConnection, user_id: i32) -> Result<PracticeTimer, AppError> {
+    practice_timers::table
+        .find(user_id)
+        .for_update()
+        .first::<PracticeTimer>(conn)
+        .map_err(|e| match e {
+            Error::NotFound => AppError::NotFound("No practice timer is running".to_owned()),
+            _ => AppError::BackendError(e.to_string()),
+        })
+}
+
+// the timer's segments in the order they were switched to
+pub fn load_segments(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> QueryResult<Vec<PracticeTimerSegment>> {
+    practice_timer_segments::table
+        .filter(practice_timer_segments::user_id.eq(user_id))
+        .order((
+            practice_timer_segments::start_secs,
+            practice_timer_segments::segment_id,
+        ))
+        .load::<PracticeTimerSegment>(conn)
+}
+
+pub fn timer_state(
+    conn: &mut PgConnection,
+    timer: PracticeTimer,
+    now: DateTime<Utc>,
+) -> Result<TimerState, AppError> {
+    let instrument = crate::instruments::load_instrument(conn, timer.user_id, timer.instrument_id)?;
+    let segments = load_segments(conn, timer.user_id)?;
+
+    Ok(TimerState {
+        elapsed_secs: elapsed_secs(&timer, now),
+        paused: timer.paused_at.is_some(),
+        piece_id: segments.last().and_then(|segment| segment.piece_id),
+        timer,
+        instrument,
+        segments,
+    })
+}
+
+pub fn start_timer(
+    conn: &mut PgConnection,
+    user_id: i32,
+    instrument_id: i32,
+    piece_id: Option<i32>,
+    now: DateTime<Utc>,
+) -> Result<PracticeTimer, AppError> {
+    let timer: PracticeTimer = diesel::insert_into(practice_timers::table)
+        .values((
+            practice_timers::user_id.eq(user_id),
+            practice_timers::instrument_id.eq(instrument_id),
+            practice_timers::started_at.eq(now),
+        ))
+        .get_result(conn)
+        .map_err(|e| match e {
+            DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                AppError::Conflict("A practice timer is already running".to_owned())
+            }
+            _ => AppError::BackendError(e.to_string()),
+        })?;
+
+    if piece_id.is_some() {
+        switch_piece(conn, &timer, piece_id, now)?;
+    }
+
+    Ok(timer)
+}
+
+pub fn pause_timer(
+    conn: &mut PgConnection,
+    timer: &PracticeTimer,
+    now: DateTime<Utc>,
+) -> Result<PracticeTimer, AppError> {
+    if timer.paused_at.is_some() {
+        return Err(AppError::Conflict(
+            "The practice timer is already paused".to_owned(),
+        ));
+    }
+
+    Ok(diesel::update(timer)
+        .set(practice_timers::paused_at.eq(now))
+        .get_result(conn)?)
+}
+
+// time spent paused is added to paused_secs, so elapsed time carries on from where it stopped
+pub fn resume_timer(
+    conn: &mut PgConnection,
+    timer: &PracticeTimer,
+    now: DateTime<Utc>,
+) -> Result<PracticeTimer, AppError> {
+    let Some(paused_at) = timer.paused_at else {
+        return Err(AppError::Conflict(
+            "The practice timer is not paused".to_owned(),
+        ));
+    };
+
+    let paused_secs = i64::from(timer.paused_secs) + (now - paused_at).num_seconds().max(0);
+
+    Ok(diesel::update(timer)
+        .set((
+            practice_timers::paused_at.eq(None::<DateTime<Utc>>),
+            practice_timers::paused_secs.eq(i32::try_from(paused_secs).unwrap_or(i32::MAX)),
+        ))
+        .get_result(conn)?)
+}
+
+// starts a new segment on the piece (or on no piece) from the current elapsed time. Switching
+// to the piece already being practiced does nothing, and switching again before any time has
+// passed replaces the segment that was just started
+pub fn switch_piece(
+    conn: &mut PgConnection,
+    timer: &PracticeTimer,
+    piece_id: Option<i32>,
+    now: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let start_secs = elapsed_secs(timer, now);
+    let last_segment = load_segments(conn, timer.user_id)?.pop();
+
+    let map_piece_err = |e| match e {
+        DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+            AppError::NotFound("Piece not found".to_owned())
+        }
+        _ => AppError::BackendError(e.to_string()),
+    };
+
+    match last_segment {
+        Some(segment) if segment.piece_id == piece_id => {}
+        Some(segment) if segment.start_secs >= start_secs => {
+            diesel::update(&segment)
+                .set(practice_timer_segments::piece_id.eq(piece_id))
+                .execute(conn)
+                .map_err(map_piece_err)?;
+        }
+        // nothing has been practiced yet, so there's no need to record a segment without a piece
+        None if piece_id.is_none() => {}
+        _ => {
+            diesel::insert_into(practice_timer_segments::table)
+                .values((
+                    practice_timer_segments::user_id.eq(timer.user_id),
+                    practice_timer_segments::piece_id.eq(piece_id),
+                    practice_timer_segments::start_secs.eq(start_secs),
+                ))
+                .execute(conn)
+                .map_err(map_piece_err)?;
+        }
+    }
+
+    Ok(())
+}
+
+// discards the timer along with its segments
+pub fn delete_timer(conn: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::delete(
+        practice_timer_segments::table.filter(practice_timer_segments::user_id.eq(user_id)),
+    )
+    .execute(conn)?;
+
+    diesel::delete(practice_timers::table.find(user_id)).execute(conn)
+}
+
+// the pieces practiced while the timer ran, in the order they were first switched to. Each
+// segment lasts until the next one starts (or the timer stops), a piece's time is the sum of
+// its segments, and it starts where it was first practiced. Minutes are rounded down, which
+// keeps every allocation within the session's own rounded down duration
+pub fn make_pieces_practiced(
+    segments: &[PracticeTimerSegment],
+    elapsed_secs: i32,
+) -> Vec<NewPiecePracticedData> {
+    // (piece_id, first start, total seconds)
+    let mut pieces: Vec<(i32, i32, i32)> = Vec::new();
+
+    for (index, segment) in segments.iter().enumerate() {
+        let Some(piece_id) = segment.piece_id else {
+            continue;
+        };
+
+        let end_secs = segments
+            .get(index + 1)
+            .map_or(elapsed_secs, |next| next.start_secs);
+        let secs = (end_secs.min(elapsed_secs) - segment.start_secs).max(0);
+
+        match pieces.iter_mut().find(|piece| piece.0 == piece_id) {
+            Some(piece) => piece.2 += secs,
+            None => pieces.push((piece_id, segment.start_secs, secs)),
+        }
+    }
+
+    pieces
+        .into_iter()
+        .map(|(piece_id, start_secs, secs)| NewPiecePracticedData {
+            piece_id,
+            duration_mins: Some((secs / 60).unsigned_abs()),
+            start_offset_mins: Some((start_secs / 60).unsigned_abs()),
+            notes: None,
+            tempo_entries: None,
+        })
+        .collect()
+}